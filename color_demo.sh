@@ -62,7 +62,7 @@ echo "📱 Emojis for visual categorization and fun"
 echo
 
 echo "💡 To see the real colorful output, run:"
-echo "   ./target/release/chain-life auth --client-id YOUR_ID --client-secret YOUR_SECRET"
+echo "   STRAVA_CLIENT_SECRET=YOUR_SECRET ./target/release/chain-life auth --client-id YOUR_ID"
 echo "   ./target/release/chain-life fetch --date 2024-01-01 --token YOUR_TOKEN --verbose"
 echo
 
@@ -41,7 +41,8 @@ echo "   - Note your Client ID and Client Secret"
 echo
 
 echo "2. Authenticate with Strava:"
-echo "   ./target/release/chain-life auth --client-id YOUR_CLIENT_ID --client-secret YOUR_CLIENT_SECRET"
+echo "   ./target/release/chain-life auth --client-id YOUR_CLIENT_ID"
+echo "   (you'll be prompted for the Client Secret, or set STRAVA_CLIENT_SECRET=YOUR_CLIENT_SECRET)"
 echo
 
 echo "3. After authentication, use the access token to fetch cycling data:"
@@ -62,7 +63,7 @@ echo
 echo "🔧 Example with fake credentials (will show URL generation):"
 echo "============================================================="
 echo
-echo "Command: ./target/release/chain-life auth --client-id 12345 --client-secret fake_secret"
+echo "Command: STRAVA_CLIENT_SECRET=fake_secret ./target/release/chain-life auth --client-id 12345"
 echo "This will generate an authorization URL that you can copy and paste into your browser."
 echo
 
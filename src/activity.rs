@@ -0,0 +1,259 @@
+use crate::credentials;
+use crate::export::{self, ExportFormat};
+use crate::strava::{self, Activity, DetailedActivity, StreamSet, STREAM_KEYS};
+use anyhow::Result;
+use colored::*;
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// Totals over a set of activities
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct Totals {
+    pub count: usize,
+    pub distance_km: f64,
+    pub moving_hours: f64,
+    pub elevation_m: f64,
+}
+
+impl Totals {
+    pub fn of<'a>(activities: impl IntoIterator<Item = &'a Activity>) -> Self {
+        let mut totals = Totals::default();
+        for activity in activities {
+            totals.count += 1;
+            totals.distance_km += activity.distance / 1000.0;
+            totals.moving_hours += activity.moving_time as f64 / 3600.0;
+            totals.elevation_m += activity.total_elevation_gain;
+        }
+        totals
+    }
+}
+
+/// Show the details of a single activity, optionally fetching and exporting its streams
+pub async fn handle_show(
+    id: i64,
+    token: Option<String>,
+    streams: bool,
+    export: Option<PathBuf>,
+    format: ExportFormat,
+    verbose: bool,
+) -> Result<()> {
+    let client = reqwest::Client::new();
+    let token = credentials::access_token(token, &[credentials::READ_ALL_ACTIVITIES]).await?;
+
+    if verbose {
+        println!(
+            "{} {}",
+            "📡 Fetching activity".cyan(),
+            id.to_string().bright_white().bold()
+        );
+    }
+
+    let activity = strava::get_activity(&client, &token, id).await?;
+    print_activity(&activity, verbose);
+
+    if !streams && export.is_none() {
+        return Ok(());
+    }
+
+    if verbose {
+        println!("{}", "📡 Fetching activity streams...".cyan());
+    }
+
+    let stream_set = strava::get_activity_streams(&client, &token, id).await?;
+
+    println!();
+    println!("{}", "📈 Streams:".bright_cyan().bold());
+    for (key, stream) in &stream_set {
+        println!(
+            "  {} {}: {} points",
+            "•".bright_blue(),
+            key.bright_white(),
+            stream.data.len().to_string().bright_green()
+        );
+    }
+
+    if let Some(path) = export {
+        let content = match format {
+            ExportFormat::Csv => {
+                let (header, rows) = streams_to_rows(&stream_set);
+                export::to_csv(&header, &rows)
+            }
+            ExportFormat::Json => serde_json::to_string_pretty(&serde_json::json!({
+                "activity": activity,
+                "streams": stream_set,
+            }))?,
+        };
+        export::write_file(&path, &content)?;
+        println!();
+        println!(
+            "{} {}",
+            "💾 Streams exported to".bright_green().bold(),
+            path.display().to_string().bright_white().bold()
+        );
+    }
+
+    Ok(())
+}
+
+fn print_activity(activity: &DetailedActivity, verbose: bool) {
+    let field = |label: &str, value: String| {
+        println!("  {} {}", format!("{label}:").cyan(), value.bright_white());
+    };
+    let optional = |label: &str, value: Option<f64>, unit: &str| {
+        if let Some(value) = value {
+            field(label, format!("{value:.1} {unit}"));
+        }
+    };
+
+    println!(
+        "{} {} ({})",
+        "🚴".bright_green(),
+        activity.name.bright_white().bold(),
+        activity.activity_type.bright_blue()
+    );
+    field("Date", activity.start_date.clone());
+    field("Distance", format!("{:.2} km", activity.distance / 1000.0));
+    field("Moving time", format_duration(activity.moving_time));
+    field("Elapsed time", format_duration(activity.elapsed_time));
+    field(
+        "Elevation gain",
+        format!("{:.0} m", activity.total_elevation_gain),
+    );
+    optional(
+        "Average speed",
+        activity.average_speed.map(|v| v * 3.6),
+        "km/h",
+    );
+    optional("Max speed", activity.max_speed.map(|v| v * 3.6), "km/h");
+    optional("Average power", activity.average_watts, "W");
+    optional(
+        "Weighted average power",
+        activity.weighted_average_watts,
+        "W",
+    );
+    optional("Max power", activity.max_watts, "W");
+    optional("Energy", activity.kilojoules, "kJ");
+    optional("Calories", activity.calories, "kcal");
+    optional("Average heart rate", activity.average_heartrate, "bpm");
+    optional("Max heart rate", activity.max_heartrate, "bpm");
+    optional("Average cadence", activity.average_cadence, "rpm");
+    if let Some(gear_id) = &activity.gear_id {
+        field("Gear", gear_id.clone());
+    }
+
+    let polyline = activity
+        .map
+        .as_ref()
+        .and_then(|map| map.polyline.as_ref().or(map.summary_polyline.as_ref()))
+        .filter(|polyline| !polyline.is_empty());
+    if let Some(polyline) = polyline {
+        if verbose {
+            field("Polyline", polyline.clone());
+        } else {
+            field(
+                "Polyline",
+                format!("{} characters (use --verbose to print)", polyline.len()),
+            );
+        }
+    }
+}
+
+/// Format a number of seconds as `H:MM:SS`
+fn format_duration(seconds: i32) -> String {
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        (seconds % 3600) / 60,
+        seconds % 60
+    )
+}
+
+/// Lay out streams as CSV columns in [`STREAM_KEYS`] order, splitting `latlng`
+/// into separate `lat` and `lng` columns
+fn streams_to_rows(streams: &StreamSet) -> (Vec<String>, Vec<Vec<String>>) {
+    let keys: Vec<&str> = STREAM_KEYS
+        .iter()
+        .copied()
+        .filter(|key| streams.contains_key(*key))
+        .collect();
+
+    let mut header = Vec::new();
+    for key in &keys {
+        if *key == "latlng" {
+            header.push("lat".to_string());
+            header.push("lng".to_string());
+        } else {
+            header.push(key.to_string());
+        }
+    }
+
+    let len = keys
+        .iter()
+        .map(|key| streams[*key].data.len())
+        .max()
+        .unwrap_or(0);
+    let rows = (0..len)
+        .map(|i| {
+            let mut row = Vec::with_capacity(header.len());
+            for key in &keys {
+                let value = streams[*key].data.get(i);
+                if *key == "latlng" {
+                    let pair = value.and_then(|v| v.as_array());
+                    row.push(pair.and_then(|p| p.first()).map(cell).unwrap_or_default());
+                    row.push(pair.and_then(|p| p.get(1)).map(cell).unwrap_or_default());
+                } else {
+                    row.push(value.map(cell).unwrap_or_default());
+                }
+            }
+            row
+        })
+        .collect();
+
+    (header, rows)
+}
+
+fn cell(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strava::ActivityStream;
+    use serde_json::json;
+
+    fn stream(data: Vec<serde_json::Value>) -> ActivityStream {
+        ActivityStream {
+            data,
+            series_type: None,
+            original_size: None,
+            resolution: None,
+        }
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(3725), "1:02:05");
+        assert_eq!(format_duration(59), "0:00:59");
+    }
+
+    #[test]
+    fn test_streams_to_rows_splits_latlng() {
+        let mut streams = StreamSet::new();
+        streams.insert("watts".to_string(), stream(vec![json!(200), json!(null)]));
+        streams.insert("time".to_string(), stream(vec![json!(0), json!(1)]));
+        streams.insert(
+            "latlng".to_string(),
+            stream(vec![json!([52.1, 4.3]), json!([52.2, 4.4])]),
+        );
+
+        let (header, rows) = streams_to_rows(&streams);
+        assert_eq!(header, vec!["time", "lat", "lng", "watts"]);
+        assert_eq!(rows[0], vec!["0", "52.1", "4.3", "200"]);
+        assert_eq!(rows[1], vec!["1", "52.2", "4.4", ""]);
+    }
+}
@@ -0,0 +1,347 @@
+use crate::chain::{self, ChainEvent};
+use crate::component::Component;
+use crate::config::Config;
+use crate::store;
+use crate::strava::Activity;
+use anyhow::{Context, Result};
+use colored::*;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+fn default_thresholds() -> Vec<f64> {
+    vec![80.0, 100.0]
+}
+
+/// Where and when to send component wear alerts, the `[alerts]` table of `config.toml`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct AlertConfig {
+    /// Wear percentages that trigger an alert
+    pub thresholds: Vec<f64>,
+    /// Show a desktop notification with `notify-send`
+    pub notify_send: bool,
+    /// URL to POST a JSON payload to
+    pub webhook_url: Option<String>,
+    /// SMTP server to send an email through
+    pub smtp: Option<SmtpConfig>,
+}
+
+impl Default for AlertConfig {
+    fn default() -> Self {
+        Self {
+            thresholds: default_thresholds(),
+            notify_send: false,
+            webhook_url: None,
+            smtp: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SmtpConfig {
+    pub host: String,
+    /// Defaults to 587, using STARTTLS
+    pub port: Option<u16>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub to: String,
+}
+
+/// A component that crossed a wear threshold
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Alert {
+    pub component: String,
+    pub kind: String,
+    pub threshold_percent: f64,
+    pub wear_percent: f64,
+    pub distance_km: f64,
+    pub rated_km: f64,
+    pub message: String,
+}
+
+/// Key under which the highest alerted threshold is remembered. The install
+/// date is part of it so that a replacement component starts afresh.
+fn alert_key(component: &Component) -> String {
+    format!("{}@{}", component.name, component.installed)
+}
+
+/// Highest threshold `wear_percent` has reached, if it's above the last one alerted
+fn crossed_threshold(
+    wear_percent: f64,
+    thresholds: &[f64],
+    last_alerted: Option<f64>,
+) -> Option<f64> {
+    thresholds
+        .iter()
+        .copied()
+        .filter(|&threshold| wear_percent >= threshold)
+        .filter(|&threshold| last_alerted.is_none_or(|last| threshold > last))
+        .max_by(f64::total_cmp)
+}
+
+/// Alerts due for `components`, given the highest threshold already alerted for each
+pub fn due_alerts(
+    components: &[Component],
+    events: &[ChainEvent],
+    activities: &[Activity],
+    thresholds: &[f64],
+    alerted: &BTreeMap<String, f64>,
+) -> Vec<(String, Alert)> {
+    components
+        .iter()
+        .filter_map(|component| {
+            let key = alert_key(component);
+            let distance_km = chain::ridden_km(component, events, activities);
+            let wear_percent = component.wear_at(distance_km) * 100.0;
+            let threshold =
+                crossed_threshold(wear_percent, thresholds, alerted.get(&key).copied())?;
+            let alert = Alert {
+                component: component.name.clone(),
+                kind: component.kind.clone(),
+                threshold_percent: threshold,
+                wear_percent,
+                distance_km,
+                rated_km: component.rated_km,
+                message: format!(
+                    "{} ({}) has reached {:.0}% of its rated distance: {:.0} of {:.0} km",
+                    component.name, component.kind, wear_percent, distance_km, component.rated_km
+                ),
+            };
+            Some((key, alert))
+        })
+        .collect()
+}
+
+fn notify_desktop(alert: &Alert) -> Result<()> {
+    let status = std::process::Command::new("notify-send")
+        .arg("chain-life: component wear")
+        .arg(&alert.message)
+        .status()
+        .context("Failed to run notify-send")?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("notify-send exited with {status}"));
+    }
+    Ok(())
+}
+
+async fn post_webhook(url: &str, alert: &Alert) -> Result<()> {
+    let response = reqwest::Client::new().post(url).json(alert).send().await?;
+    if !response.status().is_success() {
+        return Err(anyhow::anyhow!("Webhook returned {}", response.status()));
+    }
+    Ok(())
+}
+
+async fn send_email(smtp: &SmtpConfig, alert: &Alert) -> Result<()> {
+    let email = Message::builder()
+        .from(
+            smtp.from
+                .parse::<Mailbox>()
+                .context("Invalid 'from' address")?,
+        )
+        .to(smtp.to.parse::<Mailbox>().context("Invalid 'to' address")?)
+        .subject(format!(
+            "chain-life: {} at {:.0}%",
+            alert.component, alert.wear_percent
+        ))
+        .body(alert.message.clone())?;
+
+    let mut transport = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&smtp.host)?
+        .port(smtp.port.unwrap_or(587));
+    if let (Some(username), Some(password)) = (&smtp.username, &smtp.password) {
+        transport = transport.credentials(Credentials::new(username.clone(), password.clone()));
+    }
+    transport.build().send(email).await?;
+    Ok(())
+}
+
+/// Whether an alert got through: through any configured channel, or to the
+/// console alone when none are configured
+fn delivered(results: &[(&str, Result<()>)]) -> bool {
+    results.is_empty() || results.iter().any(|(_, result)| result.is_ok())
+}
+
+/// Send an alert through every configured channel, warning about the ones that
+/// fail, and return whether it got through
+async fn send(config: &AlertConfig, alert: &Alert) -> bool {
+    println!(
+        "{} {}",
+        "⚠️  Wear alert:".bright_yellow().bold(),
+        alert.message
+    );
+
+    let mut results = Vec::new();
+    if config.notify_send {
+        results.push(("notify-send", notify_desktop(alert)));
+    }
+    if let Some(url) = &config.webhook_url {
+        results.push(("webhook", post_webhook(url, alert).await));
+    }
+    if let Some(smtp) = &config.smtp {
+        results.push(("email", send_email(smtp, alert).await));
+    }
+
+    for (channel, result) in &results {
+        if let Err(error) = result {
+            eprintln!(
+                "{} {channel}: {error:#}",
+                "⚠️  Failed to send alert via".yellow()
+            );
+        }
+    }
+    delivered(&results)
+}
+
+/// Send alerts for components that crossed a wear threshold since the last
+/// check, returning how many were sent. Alerts that no channel delivered are
+/// retried on the next check.
+pub async fn check(config: &Config, activities: &[Activity]) -> Result<usize> {
+    let mut alerted: BTreeMap<String, f64> = store::load("alerts")?;
+    let due = due_alerts(
+        &config.components,
+        &chain::load_chain_events()?,
+        activities,
+        &config.alerts.thresholds,
+        &alerted,
+    );
+
+    let mut sent = 0;
+    for (key, alert) in &due {
+        if send(&config.alerts, alert).await {
+            alerted.insert(key.clone(), alert.threshold_percent);
+            sent += 1;
+        }
+    }
+    if sent > 0 {
+        store::save("alerts", &alerted)?;
+    }
+    Ok(sent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{activity, chain, day, fitted_to, on_gear};
+
+    #[test]
+    fn test_delivered() {
+        assert!(delivered(&[]));
+        assert!(delivered(&[
+            ("webhook", Err(anyhow::anyhow!("Webhook returned 500"))),
+            ("email", Ok(())),
+        ]));
+        assert!(!delivered(&[
+            ("webhook", Err(anyhow::anyhow!("Webhook returned 500"))),
+            ("email", Err(anyhow::anyhow!("Connection refused"))),
+        ]));
+    }
+
+    #[test]
+    fn test_crossed_threshold() {
+        let thresholds = [80.0, 100.0];
+        assert_eq!(crossed_threshold(50.0, &thresholds, None), None);
+        assert_eq!(crossed_threshold(85.0, &thresholds, None), Some(80.0));
+        assert_eq!(crossed_threshold(85.0, &thresholds, Some(80.0)), None);
+        assert_eq!(
+            crossed_threshold(120.0, &thresholds, Some(80.0)),
+            Some(100.0)
+        );
+        assert_eq!(crossed_threshold(120.0, &thresholds, None), Some(100.0));
+    }
+
+    #[test]
+    fn test_due_alerts() {
+        let components = vec![chain("2024-01-01", 100.0)];
+        let activities = vec![activity(1, "2024-02-01", 85.0)];
+
+        let due = due_alerts(
+            &components,
+            &[],
+            &activities,
+            &[80.0, 100.0],
+            &BTreeMap::new(),
+        );
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].0, "KMC X11@2024-01-01");
+        assert_eq!(due[0].1.threshold_percent, 80.0);
+        assert!(due[0]
+            .1
+            .message
+            .contains("85% of its rated distance: 85 of 100 km"));
+
+        let alerted = BTreeMap::from([(due[0].0.clone(), 80.0)]);
+        assert!(due_alerts(&components, &[], &activities, &[80.0, 100.0], &alerted).is_empty());
+
+        // A replacement chain installed later is alerted about again
+        let replaced = vec![chain("2024-01-15", 100.0)];
+        assert_eq!(
+            due_alerts(&replaced, &[], &activities, &[80.0, 100.0], &alerted).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_due_alerts_for_chain_in_rotation() {
+        let components = vec![fitted_to(chain("2024-01-01", 100.0), "b1")];
+        let swapped_out = vec![
+            ChainEvent::Swap {
+                chain: "KMC X11".to_string(),
+                bike: "b1".to_string(),
+                date: day("2024-01-01"),
+            },
+            ChainEvent::Swap {
+                chain: "Spare".to_string(),
+                bike: "b1".to_string(),
+                date: day("2024-02-01"),
+            },
+        ];
+        let activities = vec![
+            on_gear(activity(1, "2024-01-10", 50.0), "b1"),
+            on_gear(activity(2, "2024-02-10", 40.0), "b1"),
+        ];
+
+        // Only the rides before the swap wore the chain
+        let due = due_alerts(
+            &components,
+            &swapped_out,
+            &activities,
+            &[80.0, 100.0],
+            &BTreeMap::new(),
+        );
+        assert!(due.is_empty());
+        assert_eq!(
+            due_alerts(
+                &components,
+                &[],
+                &activities,
+                &[80.0, 100.0],
+                &BTreeMap::new()
+            )
+            .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_parse_alert_config() {
+        let config: AlertConfig = toml::from_str(
+            r#"
+            notify_send = true
+            webhook_url = "https://example.com/hook"
+
+            [smtp]
+            host = "smtp.example.com"
+            from = "chain-life <bot@example.com>"
+            to = "me@example.com"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.thresholds, vec![80.0, 100.0]);
+        assert!(config.notify_send);
+        assert_eq!(config.smtp.unwrap().port, None);
+    }
+}
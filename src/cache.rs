@@ -0,0 +1,296 @@
+use crate::config::Config;
+use crate::credentials;
+use crate::store;
+use crate::strava::{self, Activity, ActivityQuery};
+use anyhow::Result;
+use chrono::{NaiveDate, Utc};
+use colored::*;
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+
+/// Activities stored locally by `sync`, so they can be queried without the API
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ActivityCache {
+    /// Unix time of the last successful sync
+    pub synced_at: Option<i64>,
+    /// Cached activities, oldest first
+    pub activities: Vec<Activity>,
+}
+
+impl ActivityCache {
+    pub fn load() -> Result<Self> {
+        store::load("activities")
+    }
+
+    pub fn save(&self) -> Result<()> {
+        store::save("activities", self)
+    }
+
+    /// Start time of the newest cached activity
+    pub fn latest_start(&self) -> Option<i64> {
+        self.activities
+            .iter()
+            .filter_map(Activity::start_timestamp)
+            .max()
+    }
+
+    /// Insert or replace activities by ID, returning how many were new
+    pub fn merge(&mut self, activities: Vec<Activity>) -> usize {
+        let mut added = 0;
+        for activity in activities {
+            match self.activities.iter_mut().find(|a| a.id == activity.id) {
+                Some(existing) => *existing = activity,
+                None => {
+                    self.activities.push(activity);
+                    added += 1;
+                }
+            }
+        }
+        self.activities
+            .sort_by(|a, b| a.start_date.cmp(&b.start_date));
+        added
+    }
+
+    /// Remove an activity by ID, returning whether it was cached
+    pub fn remove(&mut self, id: i64) -> bool {
+        let before = self.activities.len();
+        self.activities.retain(|a| a.id != id);
+        self.activities.len() != before
+    }
+
+    /// Cached activities of the given types (all if `None`) starting on or
+    /// after `after` and before `before`
+    pub fn query(
+        &self,
+        after: Option<NaiveDate>,
+        before: Option<NaiveDate>,
+        types: Option<&[String]>,
+    ) -> Vec<&Activity> {
+        self.activities
+            .iter()
+            .filter(|a| types.is_none_or(|types| types.contains(&a.activity_type)))
+            .filter(|a| match a.date() {
+                Some(date) => {
+                    after.is_none_or(|after| date >= after)
+                        && before.is_none_or(|before| date < before)
+                }
+                None => false,
+            })
+            .collect()
+    }
+}
+
+/// Days back that `sync` re-checks when the config doesn't say otherwise
+pub const DEFAULT_RECONCILE_DAYS: u32 = 30;
+
+/// Changes made to the cache by a sync
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SyncSummary {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+}
+
+impl ActivityCache {
+    /// Apply `fetched`, a complete listing of the activities that started at or
+    /// after `since`: cached activities in that window missing from it were
+    /// deleted on Strava, and ones that differ were edited
+    pub fn reconcile(&mut self, fetched: Vec<Activity>, since: Option<i64>) -> SyncSummary {
+        let mut summary = SyncSummary::default();
+
+        if let Some(since) = since {
+            let before = self.activities.len();
+            self.activities.retain(|cached| {
+                cached.start_timestamp().is_none_or(|start| start < since)
+                    || fetched.iter().any(|a| a.id == cached.id)
+            });
+            summary.removed = before - self.activities.len();
+        }
+
+        summary.updated = fetched
+            .iter()
+            .filter(|a| {
+                self.activities
+                    .iter()
+                    .any(|cached| cached.id == a.id && cached != *a)
+            })
+            .count();
+        summary.added = self.merge(fetched);
+        summary
+    }
+}
+
+/// Fetch activities newer than the newest cached one and add them to the cache.
+/// The last `reconcile_days` are fetched again so that activities edited or
+/// deleted on Strava since they were cached are updated or removed.
+pub async fn sync(
+    cache: &mut ActivityCache,
+    token: String,
+    concurrency: usize,
+    reconcile_days: u32,
+    verbose: bool,
+) -> Result<SyncSummary> {
+    let latest = cache.latest_start();
+    let window_start =
+        (reconcile_days > 0).then(|| Utc::now().timestamp() - i64::from(reconcile_days) * 86_400);
+    let after = match (latest, window_start) {
+        (Some(latest), Some(window_start)) => Some(latest.min(window_start)),
+        (latest, _) => latest,
+    };
+
+    let query = ActivityQuery {
+        after,
+        concurrency,
+        verbose,
+        ..Default::default()
+    };
+    let activities: Vec<Activity> = strava::activities(reqwest::Client::new(), token, query)
+        .try_collect()
+        .await?;
+
+    // Everything that started after `after` was listed, so the whole fetched
+    // range can be checked for deletions, not just the reconcile window
+    let since = window_start.map(|_| after.map_or(i64::MIN, |after| after + 1));
+    let summary = cache.reconcile(activities, since);
+    cache.synced_at = Some(Utc::now().timestamp());
+    Ok(summary)
+}
+
+/// Reconcile window from `--reconcile-days`, falling back to the config file
+pub fn reconcile_days(explicit: Option<u32>) -> Result<u32> {
+    match explicit {
+        Some(days) => Ok(days),
+        None => Ok(Config::load()?
+            .reconcile_days
+            .unwrap_or(DEFAULT_RECONCILE_DAYS)),
+    }
+}
+
+pub async fn handle_sync(
+    token: Option<String>,
+    concurrency: usize,
+    reconcile_days: Option<u32>,
+    verbose: bool,
+) -> Result<()> {
+    let token = credentials::access_token(token, &[credentials::READ_ALL_ACTIVITIES]).await?;
+    let reconcile_days = self::reconcile_days(reconcile_days)?;
+    let mut cache = ActivityCache::load()?;
+
+    if verbose {
+        println!(
+            "{} {} cached activities, re-checking the last {} days",
+            "🔄 Syncing, starting from".cyan(),
+            cache.activities.len().to_string().bright_white().bold(),
+            reconcile_days.to_string().bright_white()
+        );
+    }
+
+    let summary = sync(&mut cache, token, concurrency, reconcile_days, verbose).await?;
+    cache.save()?;
+
+    println!(
+        "{} {} new, {} updated, {} deleted ({} cached)",
+        "✅ Synced".bright_green().bold(),
+        summary.added.to_string().bright_green().bold(),
+        summary.updated.to_string().bright_yellow(),
+        summary.removed.to_string().bright_red(),
+        cache.activities.len().to_string().bright_white()
+    );
+
+    crate::alert::check(&Config::load()?, &cache.activities).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{activity, day, of_type};
+
+    #[test]
+    fn test_merge_replaces_by_id_and_keeps_order() {
+        let mut cache = ActivityCache::default();
+        assert_eq!(
+            cache.merge(vec![
+                activity(2, "2024-02-01", 10.0),
+                activity(1, "2024-01-01", 20.0),
+            ]),
+            2
+        );
+        assert_eq!(cache.merge(vec![activity(2, "2024-02-01", 15.0)]), 0);
+
+        let ids: Vec<i64> = cache.activities.iter().map(|a| a.id).collect();
+        assert_eq!(ids, vec![1, 2]);
+        assert_eq!(cache.activities[1].distance, 15_000.0);
+        assert_eq!(
+            cache.latest_start(),
+            Some(
+                chrono::DateTime::parse_from_rfc3339("2024-02-01T08:00:00Z")
+                    .unwrap()
+                    .timestamp()
+            )
+        );
+    }
+
+    #[test]
+    fn test_query_filters_dates_and_types() {
+        let mut cache = ActivityCache::default();
+        cache.merge(vec![
+            activity(1, "2024-01-01", 10.0),
+            of_type(activity(2, "2024-01-15", 10.0), "Run"),
+            activity(3, "2024-02-01", 10.0),
+        ]);
+
+        let after = NaiveDate::from_ymd_opt(2024, 1, 1);
+        let before = NaiveDate::from_ymd_opt(2024, 2, 1);
+        let rides = vec!["Ride".to_string()];
+
+        let ids = |activities: Vec<&Activity>| activities.iter().map(|a| a.id).collect::<Vec<_>>();
+        assert_eq!(ids(cache.query(after, before, None)), vec![1, 2]);
+        assert_eq!(ids(cache.query(after, None, Some(&rides))), vec![1, 3]);
+    }
+
+    #[test]
+    fn test_reconcile_detects_edits_and_deletions() {
+        let mut cache = ActivityCache::default();
+        cache.merge(vec![
+            activity(1, "2024-01-01", 10.0),
+            activity(2, "2024-02-01", 10.0),
+            activity(3, "2024-02-02", 10.0),
+            activity(4, "2024-02-03", 10.0),
+        ]);
+        let since = Some(strava::day_timestamp(day("2024-02-01")));
+
+        // 2 became a virtual ride, 3 was deleted, 4 is unchanged and 5 is new
+        let summary = cache.reconcile(
+            vec![
+                of_type(activity(2, "2024-02-01", 10.0), "VirtualRide"),
+                activity(4, "2024-02-03", 10.0),
+                activity(5, "2024-02-04", 10.0),
+            ],
+            since,
+        );
+
+        assert_eq!(
+            summary,
+            SyncSummary {
+                added: 1,
+                updated: 1,
+                removed: 1
+            }
+        );
+        let ids: Vec<i64> = cache.activities.iter().map(|a| a.id).collect();
+        assert_eq!(ids, vec![1, 2, 4, 5]);
+        assert_eq!(cache.activities[1].activity_type, "VirtualRide");
+    }
+
+    #[test]
+    fn test_reconcile_without_window_only_merges() {
+        let mut cache = ActivityCache::default();
+        cache.merge(vec![activity(1, "2024-01-01", 10.0)]);
+
+        let summary = cache.reconcile(vec![activity(2, "2024-02-01", 10.0)], None);
+        assert_eq!(summary.added, 1);
+        assert_eq!(summary.removed, 0);
+        assert_eq!(cache.activities.len(), 2);
+    }
+}
@@ -0,0 +1,648 @@
+use crate::cache::ActivityCache;
+use crate::component::Component;
+use crate::config::Config;
+use crate::store;
+use crate::strava::Activity;
+use anyhow::{Context, Result};
+use chrono::{Duration, NaiveDate, Utc};
+use clap::ValueEnum;
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Days of recent riding used to estimate when a chain will reach the threshold
+const PACE_DAYS: i64 = 90;
+
+/// A chain checker reading of how far a chain has stretched
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Measurement {
+    /// Name of the component in `config.toml`
+    pub component: String,
+    pub date: NaiveDate,
+    /// Elongation in percent, e.g. 0.5
+    pub elongation: f64,
+}
+
+/// Linear fit of elongation against distance ridden on the chain
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WearFit {
+    /// Elongation in percent at 0 km
+    pub intercept: f64,
+    /// Elongation in percent per km
+    pub rate_per_km: f64,
+}
+
+impl WearFit {
+    /// Least squares fit through `(km, elongation)` points. A new chain is
+    /// taken to be unstretched, so `(0, 0)` is always included.
+    pub fn fit(points: &[(f64, f64)]) -> Option<Self> {
+        let points: Vec<(f64, f64)> = std::iter::once((0.0, 0.0))
+            .chain(points.iter().copied())
+            .collect();
+        let n = points.len() as f64;
+        let mean_km = points.iter().map(|(km, _)| km).sum::<f64>() / n;
+        let mean_elongation = points.iter().map(|(_, e)| e).sum::<f64>() / n;
+
+        let covariance: f64 = points
+            .iter()
+            .map(|(km, e)| (km - mean_km) * (e - mean_elongation))
+            .sum();
+        let variance: f64 = points.iter().map(|(km, _)| (km - mean_km).powi(2)).sum();
+        if variance == 0.0 {
+            return None;
+        }
+
+        let rate_per_km = covariance / variance;
+        (rate_per_km > 0.0).then_some(Self {
+            intercept: mean_elongation - rate_per_km * mean_km,
+            rate_per_km,
+        })
+    }
+
+    /// Distance on the chain at which it reaches `elongation` percent
+    pub fn km_at(&self, elongation: f64) -> f64 {
+        (elongation - self.intercept) / self.rate_per_km
+    }
+}
+
+fn load_measurements() -> Result<Vec<Measurement>> {
+    store::load("measurements")
+}
+
+fn find_component(config: &Config, name: &str) -> Result<Component> {
+    config
+        .components
+        .iter()
+        .find(|component| component.name == name)
+        .cloned()
+        .with_context(|| format!("No component named '{name}' in the config file"))
+}
+
+/// Distance ridden on `component`. A chain with swap events only gets the
+/// rides [`chain_usage`] attributes to it, not every ride on its bike.
+pub fn ridden_km(component: &Component, events: &[ChainEvent], activities: &[Activity]) -> f64 {
+    let in_rotation = events
+        .iter()
+        .any(|event| matches!(event, ChainEvent::Swap { chain, .. } if *chain == component.name));
+    if !in_rotation {
+        return component.distance_km(activities);
+    }
+    chain_usage(events, activities, Utc::now().date_naive())
+        .get(&component.name)
+        .map_or(0.0, |usage| usage.total_km)
+}
+
+/// Fraction of `component`'s rated distance used up, going by [`ridden_km`]
+pub fn component_wear(
+    component: &Component,
+    events: &[ChainEvent],
+    activities: &[Activity],
+) -> f64 {
+    component.wear_at(ridden_km(component, events, activities))
+}
+
+/// Distance ridden on `component` up to and including `date`
+fn distance_until(
+    component: &Component,
+    events: &[ChainEvent],
+    activities: &[Activity],
+    date: NaiveDate,
+) -> f64 {
+    let until: Vec<Activity> = activities
+        .iter()
+        .filter(|activity| activity.date().is_some_and(|day| day <= date))
+        .cloned()
+        .collect();
+    ridden_km(component, events, &until)
+}
+
+pub fn handle_measure(name: String, elongation: f64, date: Option<String>) -> Result<()> {
+    if !(0.0..5.0).contains(&elongation) {
+        return Err(anyhow::anyhow!(
+            "Elongation is a percentage such as 0.5, got {elongation}"
+        ));
+    }
+    find_component(&Config::load()?, &name)?;
+
+    let date = match date {
+        Some(date) => crate::parse_date(&date)?,
+        None => Utc::now().date_naive(),
+    };
+
+    let mut measurements = load_measurements()?;
+    measurements.push(Measurement {
+        component: name.clone(),
+        date,
+        elongation,
+    });
+    measurements.sort_by_key(|measurement| measurement.date);
+    store::save("measurements", &measurements)?;
+
+    println!(
+        "{} {} at {}% on {}",
+        "📏 Measurement logged:".bright_green().bold(),
+        name.bright_white().bold(),
+        elongation.to_string().bright_white(),
+        date
+    );
+    Ok(())
+}
+
+pub fn handle_log(name: Option<String>) -> Result<()> {
+    let measurements: Vec<Measurement> = load_measurements()?
+        .into_iter()
+        .filter(|measurement| {
+            name.as_ref()
+                .is_none_or(|name| &measurement.component == name)
+        })
+        .collect();
+    if measurements.is_empty() {
+        println!(
+            "{}",
+            "No measurements logged. Use 'chain-life chain measure' to add one.".yellow()
+        );
+        return Ok(());
+    }
+
+    let config = Config::load()?;
+    let cache = ActivityCache::load()?;
+    let events = load_chain_events()?;
+
+    println!("{}", "📏 Chain measurements:".bright_cyan().bold());
+    for measurement in &measurements {
+        let distance = find_component(&config, &measurement.component)
+            .ok()
+            .map(|component| {
+                format!(
+                    " at {:.0} km",
+                    distance_until(&component, &events, &cache.activities, measurement.date)
+                )
+            })
+            .unwrap_or_default();
+        println!(
+            "  {} {} {}: {}%{}",
+            "•".bright_blue(),
+            measurement.date,
+            measurement.component.bright_white().bold(),
+            measurement.elongation.to_string().bright_white(),
+            distance.dimmed()
+        );
+    }
+    Ok(())
+}
+
+pub fn handle_predict(name: String, threshold: f64) -> Result<()> {
+    let config = Config::load()?;
+    let component = find_component(&config, &name)?;
+    let cache = ActivityCache::load()?;
+    if cache.activities.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No cached activities. Run 'chain-life sync' first"
+        ));
+    }
+
+    let events = load_chain_events()?;
+
+    // Readings from before the chain was fitted belong to a previous chain
+    let points: Vec<(f64, f64)> = load_measurements()?
+        .iter()
+        .filter(|m| m.component == name && m.date >= component.installed)
+        .map(|m| {
+            (
+                distance_until(&component, &events, &cache.activities, m.date),
+                m.elongation,
+            )
+        })
+        .collect();
+    let fit = WearFit::fit(&points).with_context(|| {
+        format!("Not enough measurements of '{name}' with increasing distance to fit a wear rate")
+    })?;
+
+    let today = Utc::now().date_naive();
+    let current_km = ridden_km(&component, &events, &cache.activities);
+    let replace_km = fit.km_at(threshold);
+    let remaining_km = replace_km - current_km;
+
+    println!("{} {}", "🔗".bright_cyan(), name.bright_white().bold());
+    println!(
+        "  {} {:.0} km over {} measurements",
+        "Ridden:".cyan(),
+        current_km,
+        points.len()
+    );
+    println!(
+        "  {} {:.3}% per 1000 km",
+        "Wear rate:".cyan(),
+        fit.rate_per_km * 1000.0
+    );
+    println!(
+        "  {} {:.2}%",
+        "Estimated now:".cyan(),
+        fit.intercept + fit.rate_per_km * current_km
+    );
+
+    if remaining_km <= 0.0 {
+        println!(
+            "  {}",
+            format!("Past the {threshold}% replacement threshold, replace the chain now")
+                .bright_red()
+                .bold()
+        );
+        return Ok(());
+    }
+
+    println!(
+        "  {} after ~{:.0} km, {} to go",
+        format!("Replace at {threshold}%:").cyan(),
+        replace_km,
+        format!("{remaining_km:.0} km").bright_green().bold()
+    );
+
+    let recent: Vec<Activity> = cache
+        .activities
+        .iter()
+        .filter(|a| {
+            a.date()
+                .is_some_and(|day| day > today - Duration::days(PACE_DAYS))
+        })
+        .cloned()
+        .collect();
+    let daily_km = ridden_km(&component, &events, &recent) / PACE_DAYS as f64;
+    if daily_km > 0.0 {
+        let days = (remaining_km / daily_km).ceil() as i64;
+        println!(
+            "  {} around {} at {:.1} km/day",
+            "Expected:".cyan(),
+            (today + Duration::days(days))
+                .to_string()
+                .bright_white()
+                .bold(),
+            daily_km
+        );
+    }
+    Ok(())
+}
+
+/// How a chain was treated
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Treatment {
+    Wax,
+    Lube,
+}
+
+/// Something that happened to one of the chains in rotation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum ChainEvent {
+    /// The chain was put on a bike, replacing whatever chain was on it
+    Swap {
+        chain: String,
+        /// Strava gear ID of the bike
+        bike: String,
+        date: NaiveDate,
+    },
+    /// The chain was waxed or lubed
+    Treat {
+        chain: String,
+        treatment: Treatment,
+        date: NaiveDate,
+    },
+}
+
+impl ChainEvent {
+    fn chain(&self) -> &str {
+        match self {
+            Self::Swap { chain, .. } | Self::Treat { chain, .. } => chain,
+        }
+    }
+
+    fn date(&self) -> NaiveDate {
+        match self {
+            Self::Swap { date, .. } | Self::Treat { date, .. } => *date,
+        }
+    }
+}
+
+/// Distance attributed to one chain of a rotation
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChainUsage {
+    pub total_km: f64,
+    /// Distance since the last treatment, or since first fitted if never treated
+    pub since_treatment_km: f64,
+    pub last_treatment: Option<(Treatment, NaiveDate)>,
+    /// Bike the chain is on now, if any
+    pub bike: Option<String>,
+}
+
+/// Bike `chain` was on at the end of `date`, according to the swaps in `events`
+fn bike_of(events: &[ChainEvent], chain: &str, date: NaiveDate) -> Option<String> {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            ChainEvent::Swap {
+                chain: swapped,
+                bike,
+                date: swapped_on,
+            } if swapped == chain && *swapped_on <= date => Some((*swapped_on, bike)),
+            _ => None,
+        })
+        .max_by_key(|(swapped_on, _)| *swapped_on)
+        .map(|(_, bike)| bike.clone())
+}
+
+/// Chain that was on `bike` at the end of `date`: the last one swapped onto it,
+/// provided it hasn't moved to another bike since
+fn chain_on(events: &[ChainEvent], bike: &str, date: NaiveDate) -> Option<String> {
+    let chain = events
+        .iter()
+        .filter_map(|event| match event {
+            ChainEvent::Swap {
+                chain,
+                bike: swapped_onto,
+                date: swapped_on,
+            } if swapped_onto == bike && *swapped_on <= date => Some((*swapped_on, chain)),
+            _ => None,
+        })
+        .max_by_key(|(swapped_on, _)| *swapped_on)?
+        .1;
+    (bike_of(events, chain, date).as_deref() == Some(bike)).then(|| chain.clone())
+}
+
+/// Attribute each activity's distance to the chain that was on its bike that
+/// day. Swaps and treatments apply from their day on.
+pub fn chain_usage(
+    events: &[ChainEvent],
+    activities: &[Activity],
+    today: NaiveDate,
+) -> BTreeMap<String, ChainUsage> {
+    let mut usage: BTreeMap<String, ChainUsage> = BTreeMap::new();
+    for event in events {
+        let entry = usage.entry(event.chain().to_string()).or_default();
+        if let ChainEvent::Treat {
+            treatment, date, ..
+        } = event
+        {
+            if entry.last_treatment.is_none_or(|(_, last)| *date >= last) {
+                entry.last_treatment = Some((*treatment, *date));
+            }
+        }
+    }
+
+    for activity in activities {
+        let (Some(bike), Some(date)) = (&activity.gear_id, activity.date()) else {
+            continue;
+        };
+        let Some(chain) = chain_on(events, bike, date) else {
+            continue;
+        };
+
+        let entry = usage.entry(chain).or_default();
+        let km = activity.distance / 1000.0;
+        entry.total_km += km;
+        if entry
+            .last_treatment
+            .is_none_or(|(_, treated_on)| date >= treated_on)
+        {
+            entry.since_treatment_km += km;
+        }
+    }
+
+    for (chain, entry) in usage.iter_mut() {
+        entry.bike = bike_of(events, chain, today)
+            .filter(|bike| chain_on(events, bike, today).as_deref() == Some(chain.as_str()));
+    }
+    usage
+}
+
+pub fn load_chain_events() -> Result<Vec<ChainEvent>> {
+    store::load("chain_events")
+}
+
+fn save_chain_event(event: ChainEvent) -> Result<()> {
+    let mut events = load_chain_events()?;
+    events.push(event);
+    // Stable, so same-day events keep the order they were recorded in
+    events.sort_by_key(ChainEvent::date);
+    store::save("chain_events", &events)
+}
+
+fn event_date(date: Option<String>) -> Result<NaiveDate> {
+    match date {
+        Some(date) => crate::parse_date(&date),
+        None => Ok(Utc::now().date_naive()),
+    }
+}
+
+pub fn handle_swap(chain: String, bike: String, date: Option<String>) -> Result<()> {
+    let date = event_date(date)?;
+    save_chain_event(ChainEvent::Swap {
+        chain: chain.clone(),
+        bike: bike.clone(),
+        date,
+    })?;
+
+    println!(
+        "{} {} on {} from {}",
+        "🔁 Chain swapped:".bright_green().bold(),
+        chain.bright_white().bold(),
+        bike.bright_blue(),
+        date
+    );
+    Ok(())
+}
+
+pub fn handle_treat(chain: String, treatment: Treatment, date: Option<String>) -> Result<()> {
+    let date = event_date(date)?;
+    save_chain_event(ChainEvent::Treat {
+        chain: chain.clone(),
+        treatment,
+        date,
+    })?;
+
+    let verb = match treatment {
+        Treatment::Wax => "🕯️  Chain waxed:",
+        Treatment::Lube => "🛢️  Chain lubed:",
+    };
+    println!(
+        "{} {} on {}",
+        verb.bright_green().bold(),
+        chain.bright_white().bold(),
+        date
+    );
+    Ok(())
+}
+
+pub fn handle_rotation() -> Result<()> {
+    let events = load_chain_events()?;
+    if events.is_empty() {
+        println!(
+            "{}",
+            "No chain swaps recorded. Use 'chain-life chain swap' to add one.".yellow()
+        );
+        return Ok(());
+    }
+
+    let cache = ActivityCache::load()?;
+    let usage = chain_usage(&events, &cache.activities, Utc::now().date_naive());
+
+    println!("{}", "🔁 Chain rotation:".bright_cyan().bold());
+    for (chain, usage) in &usage {
+        let bike = match &usage.bike {
+            Some(bike) => format!("on {bike}").bright_blue(),
+            None => "off the bike".dimmed(),
+        };
+        let treated = match usage.last_treatment {
+            Some((Treatment::Wax, date)) => format!("since waxing on {date}"),
+            Some((Treatment::Lube, date)) => format!("since lubing on {date}"),
+            None => "never treated".to_string(),
+        };
+        println!(
+            "  {} {} ({}): {} total, {} {}",
+            "•".bright_blue(),
+            chain.bright_white().bold(),
+            bike,
+            format!("{:.0} km", usage.total_km).bright_white(),
+            format!("{:.0} km", usage.since_treatment_km)
+                .bright_green()
+                .bold(),
+            treated
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{activity, chain, day, fitted_to, on_gear};
+
+    #[test]
+    fn test_fit_through_origin_readings() {
+        let fit = WearFit::fit(&[(2000.0, 0.5), (3000.0, 0.75)]).unwrap();
+        assert!(fit.intercept.abs() < 1e-9);
+        assert!((fit.rate_per_km - 0.00025).abs() < 1e-12);
+        assert!((fit.km_at(1.0) - 4000.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_fit_needs_distance() {
+        assert_eq!(WearFit::fit(&[]), None);
+        assert_eq!(WearFit::fit(&[(0.0, 0.25)]), None);
+        // Readings that shrink with distance give no usable rate
+        assert_eq!(WearFit::fit(&[(1000.0, 0.5), (2000.0, -0.5)]), None);
+    }
+
+    #[test]
+    fn test_distance_until() {
+        let component = chain("2024-01-01", 3000.0);
+        let activities = vec![
+            activity(1, "2024-02-01", 40.0),
+            activity(2, "2024-03-01", 60.0),
+        ];
+
+        assert_eq!(
+            distance_until(&component, &[], &activities, day("2024-01-31")),
+            0.0
+        );
+        assert_eq!(
+            distance_until(&component, &[], &activities, day("2024-02-01")),
+            40.0
+        );
+        assert_eq!(
+            distance_until(&component, &[], &activities, day("2024-03-15")),
+            100.0
+        );
+    }
+
+    fn swap(chain: &str, bike: &str, date: &str) -> ChainEvent {
+        ChainEvent::Swap {
+            chain: chain.to_string(),
+            bike: bike.to_string(),
+            date: day(date),
+        }
+    }
+
+    #[test]
+    fn test_chain_usage_follows_swaps_and_treatments() {
+        let events = vec![
+            swap("A", "b1", "2024-01-01"),
+            swap("B", "b1", "2024-02-01"),
+            ChainEvent::Treat {
+                chain: "A".to_string(),
+                treatment: Treatment::Wax,
+                date: day("2024-02-15"),
+            },
+            swap("A", "b1", "2024-03-01"),
+        ];
+        let activities = vec![
+            on_gear(activity(1, "2024-01-10", 50.0), "b1"),
+            on_gear(activity(2, "2024-02-10", 30.0), "b1"),
+            on_gear(activity(3, "2024-03-10", 20.0), "b1"),
+            on_gear(activity(4, "2024-03-11", 100.0), "b2"),
+        ];
+
+        let usage = chain_usage(&events, &activities, day("2024-04-01"));
+        assert_eq!(usage["A"].total_km, 70.0);
+        assert_eq!(usage["A"].since_treatment_km, 20.0);
+        assert_eq!(
+            usage["A"].last_treatment,
+            Some((Treatment::Wax, day("2024-02-15")))
+        );
+        assert_eq!(usage["A"].bike.as_deref(), Some("b1"));
+        assert_eq!(usage["B"].total_km, 30.0);
+        assert_eq!(usage["B"].since_treatment_km, 30.0);
+        assert_eq!(usage["B"].bike, None);
+    }
+
+    #[test]
+    fn test_ridden_km_of_chain_in_rotation() {
+        let mut component = Component {
+            name: "A".to_string(),
+            ..fitted_to(chain("2024-01-01", 3000.0), "b1")
+        };
+        let events = vec![swap("A", "b1", "2024-01-01"), swap("B", "b1", "2024-02-01")];
+        let activities = vec![
+            on_gear(activity(1, "2024-01-10", 50.0), "b1"),
+            on_gear(activity(2, "2024-02-10", 30.0), "b1"),
+        ];
+
+        assert_eq!(ridden_km(&component, &events, &activities), 50.0);
+        assert_eq!(
+            distance_until(&component, &events, &activities, day("2024-01-31")),
+            50.0
+        );
+        // Without swap events every ride on the bike counts
+        component.name = "C".to_string();
+        assert_eq!(ridden_km(&component, &events, &activities), 80.0);
+    }
+
+    #[test]
+    fn test_chain_moved_to_another_bike() {
+        let events = vec![swap("A", "b1", "2024-01-01"), swap("A", "b2", "2024-02-01")];
+        let activities = vec![
+            on_gear(activity(1, "2024-01-10", 10.0), "b1"),
+            on_gear(activity(2, "2024-02-10", 20.0), "b1"),
+            on_gear(activity(3, "2024-02-11", 40.0), "b2"),
+        ];
+
+        let usage = chain_usage(&events, &activities, day("2024-03-01"));
+        assert_eq!(usage["A"].total_km, 50.0);
+        assert_eq!(usage["A"].bike.as_deref(), Some("b2"));
+    }
+
+    #[test]
+    fn test_chain_event_serialization() {
+        let event = ChainEvent::Treat {
+            chain: "A".to_string(),
+            treatment: Treatment::Lube,
+            date: day("2024-02-15"),
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"event": "treat", "chain": "A", "treatment": "lube", "date": "2024-02-15"})
+        );
+        assert_eq!(serde_json::from_value::<ChainEvent>(json).unwrap(), event);
+    }
+}
@@ -0,0 +1,306 @@
+use crate::strava::Activity;
+use chrono::{Datelike, Days, Months, NaiveDate};
+use clap::ValueEnum;
+use colored::*;
+use std::collections::BTreeMap;
+
+/// Terminal width assumed when it can't be detected (e.g. output is piped)
+const DEFAULT_WIDTH: usize = 80;
+
+/// Partial block characters, from one eighth to a full cell
+const EIGHTHS: [char; 8] = ['▏', '▎', '▍', '▌', '▋', '▊', '▉', '█'];
+
+/// Sparkline levels, from lowest to highest
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Heatmap intensity levels; index 0 is a day without activities
+const SHADES: [char; 5] = ['·', '░', '▒', '▓', '█'];
+
+/// How to visualise distance over time
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ChartKind {
+    /// Horizontal bar per week or month
+    Bars,
+    /// Single-line sparkline of weekly or monthly distance
+    Sparkline,
+    /// Calendar heatmap of days with activities
+    Heatmap,
+}
+
+/// Bucket size for bar charts and sparklines
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Period {
+    Week,
+    Month,
+}
+
+impl Period {
+    /// First day of the week (Monday) or month containing `date`
+    fn start_of(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Week => date - Days::new(date.weekday().num_days_from_monday() as u64),
+            Period::Month => date.with_day(1).unwrap(),
+        }
+    }
+
+    fn next(self, start: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Week => start + Days::new(7),
+            Period::Month => start + Months::new(1),
+        }
+    }
+
+    fn label(self, start: NaiveDate) -> String {
+        match self {
+            Period::Week => start.format("%G-W%V").to_string(),
+            Period::Month => start.format("%Y-%m").to_string(),
+        }
+    }
+}
+
+/// Usable terminal width in columns
+pub fn terminal_width() -> usize {
+    terminal_size::terminal_size()
+        .map(|(width, _)| width.0 as usize)
+        .unwrap_or(DEFAULT_WIDTH)
+}
+
+/// Total kilometers per day
+pub fn daily_distances(activities: &[Activity]) -> BTreeMap<NaiveDate, f64> {
+    let mut days = BTreeMap::new();
+    for activity in activities {
+        if let Some(date) = activity.date() {
+            *days.entry(date).or_insert(0.0) += activity.distance / 1000.0;
+        }
+    }
+    days
+}
+
+/// Total kilometers per week or month between `start` and `end`, including
+/// empty buckets, labelled like `2024-W03` or `2024-01`
+pub fn bucket_distances(
+    days: &BTreeMap<NaiveDate, f64>,
+    period: Period,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> Vec<(String, f64)> {
+    let mut buckets = Vec::new();
+    let mut bucket = period.start_of(start);
+    while bucket <= end {
+        let next = period.next(bucket);
+        let km = days.range(bucket..next).map(|(_, km)| km).sum();
+        buckets.push((period.label(bucket), km));
+        bucket = next;
+    }
+    buckets
+}
+
+/// Render one horizontal bar per bucket, scaled to fit `width` columns
+pub fn bar_chart(buckets: &[(String, f64)], width: usize) -> Vec<String> {
+    let label_width = buckets
+        .iter()
+        .map(|(label, _)| label.len())
+        .max()
+        .unwrap_or(0);
+    let max = buckets.iter().map(|(_, km)| *km).fold(0.0, f64::max);
+    // Label, spaces and a right-aligned " 1234.5 km" value
+    let bar_width = width.saturating_sub(label_width + 13).max(1);
+
+    buckets
+        .iter()
+        .map(|(label, km)| {
+            let bar = if max > 0.0 {
+                bar(km / max * bar_width as f64)
+            } else {
+                String::new()
+            };
+            format!(
+                "{label:<label_width$} {} {:>7.1} km",
+                format!("{bar:<bar_width$}").bright_green(),
+                km
+            )
+        })
+        .collect()
+}
+
+/// A bar `cells` wide, using partial blocks for the fractional part
+fn bar(cells: f64) -> String {
+    let eighths = (cells * 8.0).round() as usize;
+    let mut bar = "█".repeat(eighths / 8);
+    let remainder = eighths % 8;
+    if remainder > 0 {
+        bar.push(EIGHTHS[remainder - 1]);
+    }
+    bar
+}
+
+/// Render values as a sparkline, keeping only the most recent `width` values
+pub fn sparkline(values: &[f64], width: usize) -> String {
+    let values = &values[values.len().saturating_sub(width)..];
+    let max = values.iter().copied().fold(0.0, f64::max);
+
+    values
+        .iter()
+        .map(|value| {
+            if max <= 0.0 {
+                SPARKS[0]
+            } else {
+                let level = (value / max * (SPARKS.len() - 1) as f64).round() as usize;
+                SPARKS[level.min(SPARKS.len() - 1)]
+            }
+        })
+        .collect()
+}
+
+/// Render a GitHub-style calendar heatmap: one row per weekday, one column per
+/// week, with a month label row on top. Only the most recent weeks that fit
+/// in `width` columns are shown.
+pub fn heatmap(
+    days: &BTreeMap<NaiveDate, f64>,
+    start: NaiveDate,
+    end: NaiveDate,
+    width: usize,
+) -> Vec<String> {
+    const ROW_LABELS: [&str; 7] = ["Mon", "   ", "Wed", "   ", "Fri", "   ", "Sun"];
+
+    let last_week = Period::Week.start_of(end);
+    let mut first_week = Period::Week.start_of(start);
+    let max_weeks = width.saturating_sub(4).max(1) as u64;
+    let weeks = (last_week - first_week).num_weeks() as u64 + 1;
+    if weeks > max_weeks {
+        first_week = last_week - Days::new((max_weeks - 1) * 7);
+    }
+
+    let max = days.values().copied().fold(0.0, f64::max);
+    let weeks: Vec<NaiveDate> = first_week
+        .iter_weeks()
+        .take_while(|week| *week <= last_week)
+        .collect();
+
+    let mut months = String::from("    ");
+    let mut previous_month = None;
+    let mut skip = 0;
+    for week in &weeks {
+        if skip > 0 {
+            skip -= 1;
+            continue;
+        }
+        if previous_month != Some(week.month()) {
+            let label = week.format("%b").to_string();
+            skip = label.chars().count() - 1;
+            months.push_str(&label);
+            previous_month = Some(week.month());
+        } else {
+            months.push(' ');
+        }
+    }
+
+    let mut lines = vec![months.trim_end().to_string()];
+    for (weekday, row_label) in ROW_LABELS.iter().enumerate() {
+        let mut line = format!("{row_label} ");
+        for week in &weeks {
+            let day = *week + Days::new(weekday as u64);
+            if day < start || day > end {
+                line.push(' ');
+                continue;
+            }
+            let km = days.get(&day).copied().unwrap_or(0.0);
+            let level = if km <= 0.0 || max <= 0.0 {
+                0
+            } else {
+                ((km / max * 4.0).ceil() as usize).clamp(1, 4)
+            };
+            let cell = SHADES[level].to_string();
+            line.push_str(&if level == 0 {
+                cell.dimmed().to_string()
+            } else {
+                cell.bright_green().to_string()
+            });
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_bucket_distances_by_week() {
+        let mut days = BTreeMap::new();
+        days.insert(date(2024, 1, 1), 10.0);
+        days.insert(date(2024, 1, 7), 5.0);
+        days.insert(date(2024, 1, 15), 20.0);
+
+        let buckets = bucket_distances(&days, Period::Week, date(2024, 1, 3), date(2024, 1, 20));
+        assert_eq!(
+            buckets,
+            vec![
+                ("2024-W01".to_string(), 15.0),
+                ("2024-W02".to_string(), 0.0),
+                ("2024-W03".to_string(), 20.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bucket_distances_by_month() {
+        let mut days = BTreeMap::new();
+        days.insert(date(2024, 1, 31), 10.0);
+        days.insert(date(2024, 3, 1), 5.0);
+
+        let buckets = bucket_distances(&days, Period::Month, date(2024, 1, 15), date(2024, 3, 2));
+        assert_eq!(buckets.len(), 3);
+        assert_eq!(buckets[0], ("2024-01".to_string(), 10.0));
+        assert_eq!(buckets[1], ("2024-02".to_string(), 0.0));
+    }
+
+    #[test]
+    fn test_bar_uses_partial_blocks() {
+        assert_eq!(bar(2.5), "██▌");
+        assert_eq!(bar(0.0), "");
+    }
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(sparkline(&[0.0, 50.0, 100.0], 10), "▁▅█");
+        assert_eq!(sparkline(&[0.0, 50.0, 100.0], 2), "▅█");
+        assert_eq!(sparkline(&[0.0, 0.0], 10), "▁▁");
+    }
+
+    /// `line` without the ANSI color codes `colored` may have added
+    fn strip_ansi(line: &str) -> String {
+        let mut stripped = String::new();
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                // Skip the rest of the escape sequence, which ends in `m`
+                chars.by_ref().find(|&c| c == 'm');
+            } else {
+                stripped.push(c);
+            }
+        }
+        stripped
+    }
+
+    #[test]
+    fn test_heatmap_layout() {
+        let mut days = BTreeMap::new();
+        days.insert(date(2024, 1, 1), 40.0);
+        days.insert(date(2024, 1, 10), 10.0);
+
+        let lines: Vec<String> = heatmap(&days, date(2024, 1, 1), date(2024, 1, 14), 80)
+            .iter()
+            .map(|line| strip_ansi(line))
+            .collect();
+        assert_eq!(lines.len(), 8);
+        assert_eq!(lines[0], "    Jan");
+        assert_eq!(lines[1], "Mon █·");
+        assert_eq!(lines[3], "Wed ·░");
+    }
+}
@@ -0,0 +1,99 @@
+use crate::strava::Activity;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+fn default_activity_types() -> String {
+    "cycling".to_string()
+}
+
+/// A wearing part such as a chain or cassette, configured in `config.toml`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Component {
+    pub name: String,
+    /// Kind of part, e.g. `chain`, `cassette` or `tyre`
+    pub kind: String,
+    /// Day the component was fitted
+    pub installed: NaiveDate,
+    /// Distance after which the component should be replaced
+    pub rated_km: f64,
+    /// Strava gear ID of the bike it's fitted to; all bikes count if unset
+    pub gear_id: Option<String>,
+    /// Activity types that wear the component (comma-separated), as for 'fetch'
+    #[serde(default = "default_activity_types")]
+    pub activity_types: String,
+    /// What the component cost, for cost per km
+    pub price: Option<f64>,
+}
+
+impl Component {
+    /// Whether `activity` put distance on this component
+    pub fn applies_to(&self, activity: &Activity, allowed_types: &[String]) -> bool {
+        allowed_types.contains(&activity.activity_type)
+            && activity.date().is_some_and(|date| date >= self.installed)
+            && self
+                .gear_id
+                .as_ref()
+                .is_none_or(|gear_id| activity.gear_id.as_ref() == Some(gear_id))
+    }
+
+    /// Kilometers ridden on this component
+    pub fn distance_km(&self, activities: &[Activity]) -> f64 {
+        let allowed_types = crate::parse_activity_types(&self.activity_types).unwrap_or_default();
+        activities
+            .iter()
+            .filter(|activity| self.applies_to(activity, &allowed_types))
+            .map(|activity| activity.distance)
+            .sum::<f64>()
+            / 1000.0
+    }
+
+    /// Fraction of the rated distance used up after riding `distance_km` on
+    /// it, 1.0 meaning due for replacement
+    pub fn wear_at(&self, distance_km: f64) -> f64 {
+        if self.rated_km <= 0.0 {
+            return 0.0;
+        }
+        distance_km / self.rated_km
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{activity, chain, fitted_to, of_type, on_gear};
+
+    #[test]
+    fn test_wear_counts_rides_since_installation_on_bike() {
+        let activities = vec![
+            on_gear(activity(1, "2024-02-28", 100.0), "b1"),
+            on_gear(activity(2, "2024-03-01", 600.0), "b1"),
+            on_gear(activity(3, "2024-03-02", 300.0), "b2"),
+            on_gear(
+                of_type(activity(4, "2024-03-03", 900.0), "GravelRide"),
+                "b1",
+            ),
+            of_type(activity(5, "2024-03-04", 10.0), "Run"),
+        ];
+        let any_bike = chain("2024-03-01", 3000.0);
+        let on_b1 = fitted_to(any_bike.clone(), "b1");
+
+        assert_eq!(on_b1.distance_km(&activities), 1500.0);
+        assert_eq!(on_b1.wear_at(on_b1.distance_km(&activities)), 0.5);
+        assert_eq!(any_bike.distance_km(&activities), 1800.0);
+    }
+
+    #[test]
+    fn test_parse_component_config() {
+        let component: Component = toml::from_str(
+            r#"
+            name = "KMC X11"
+            kind = "chain"
+            installed = "2024-03-01"
+            rated_km = 3000
+            gear_id = "b1"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(component, fitted_to(chain("2024-03-01", 3000.0), "b1"));
+    }
+}
@@ -0,0 +1,72 @@
+use crate::alert::AlertConfig;
+use crate::component::Component;
+use crate::cost::Bike;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Environment variable overriding the location of the config file
+const CONFIG_ENV: &str = "CHAIN_LIFE_CONFIG";
+
+/// User settings read from `config.toml`
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct Config {
+    /// Functional threshold power in watts, used for power-based training stress
+    pub ftp: Option<f64>,
+    /// Lactate threshold heart rate in bpm, used for heart rate-based training stress
+    pub threshold_hr: Option<f64>,
+    /// Wearing parts whose distance is tracked
+    pub components: Vec<Component>,
+    /// Bike purchases, for cost per km
+    pub bikes: Vec<Bike>,
+    /// Days back that `sync` re-checks cached activities for edits and deletions
+    pub reconcile_days: Option<u32>,
+    /// Component wear alerts sent after syncing
+    pub alerts: AlertConfig,
+}
+
+impl Config {
+    /// Load the config file, falling back to defaults if it doesn't exist
+    pub fn load() -> Result<Self> {
+        let path = config_path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        toml::from_str(&content)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))
+    }
+}
+
+/// Location of the config file: `$CHAIN_LIFE_CONFIG`, or `chain-life/config.toml`
+/// in the platform's config directory
+pub fn config_path() -> Result<PathBuf> {
+    if let Some(path) = std::env::var_os(CONFIG_ENV) {
+        return Ok(PathBuf::from(path));
+    }
+
+    let dir = dirs::config_dir().context("Could not determine the config directory")?;
+    Ok(dir.join("chain-life").join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config: Config = toml::from_str("ftp = 250\nthreshold_hr = 168").unwrap();
+        assert_eq!(config.ftp, Some(250.0));
+        assert_eq!(config.threshold_hr, Some(168.0));
+    }
+
+    #[test]
+    fn test_parse_empty_config() {
+        let config: Config = toml::from_str("").unwrap();
+        assert!(config.ftp.is_none());
+        assert!(config.threshold_hr.is_none());
+    }
+}
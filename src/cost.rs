@@ -0,0 +1,315 @@
+use crate::cache::ActivityCache;
+use crate::chain::{self, ChainEvent};
+use crate::component::Component;
+use crate::config::Config;
+use crate::service::{self, ServiceEvent};
+use crate::strava::Activity;
+use anyhow::Result;
+use chrono::NaiveDate;
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A bike's purchase, configured as `[[bikes]]` in `config.toml`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Bike {
+    /// Strava gear ID of the bike
+    pub gear_id: String,
+    pub name: Option<String>,
+    pub price: Option<f64>,
+    /// Day the bike was bought; its price only counts towards that year's report
+    pub purchased: Option<NaiveDate>,
+}
+
+/// Money spent and distance ridden for one bike or component kind
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CostLine {
+    pub label: String,
+    pub spent: f64,
+    pub distance_km: f64,
+}
+
+impl CostLine {
+    pub fn per_km(&self) -> Option<f64> {
+        (self.distance_km > 0.0).then(|| self.spent / self.distance_km)
+    }
+}
+
+/// Whether `date` falls in `period` (start and end included), which is
+/// unbounded if `None`. Undated spending only counts without a period.
+fn in_period(date: Option<NaiveDate>, period: Option<(NaiveDate, NaiveDate)>) -> bool {
+    match (date, period) {
+        (_, None) => true,
+        (Some(date), Some((start, end))) => date >= start && date <= end,
+        (None, Some(_)) => false,
+    }
+}
+
+fn activities_in(activities: &[Activity], period: Option<(NaiveDate, NaiveDate)>) -> Vec<Activity> {
+    activities
+        .iter()
+        .filter(|a| in_period(a.date(), period))
+        .cloned()
+        .collect()
+}
+
+/// Line for `gear_id`, labelled with the bike's configured name if it has one
+fn bike_line<'a>(
+    lines: &'a mut BTreeMap<String, CostLine>,
+    bikes: &[Bike],
+    gear_id: &str,
+) -> &'a mut CostLine {
+    lines
+        .entry(gear_id.to_string())
+        .or_insert_with(|| CostLine {
+            label: bikes
+                .iter()
+                .find(|bike| bike.gear_id == gear_id)
+                .and_then(|bike| bike.name.clone())
+                .unwrap_or_else(|| gear_id.to_string()),
+            ..Default::default()
+        })
+}
+
+/// Cost per km of each bike: its price, the prices of components fitted to it
+/// and its services, over the distance ridden on it
+pub fn bike_costs(
+    bikes: &[Bike],
+    components: &[Component],
+    services: &[ServiceEvent],
+    activities: &[Activity],
+    period: Option<(NaiveDate, NaiveDate)>,
+) -> Vec<CostLine> {
+    let mut lines: BTreeMap<String, CostLine> = BTreeMap::new();
+    for bike in bikes {
+        let entry = bike_line(&mut lines, bikes, &bike.gear_id);
+        if in_period(bike.purchased, period) {
+            entry.spent += bike.price.unwrap_or(0.0);
+        }
+    }
+    for component in components {
+        if let Some(gear_id) = &component.gear_id {
+            let entry = bike_line(&mut lines, bikes, gear_id);
+            if in_period(Some(component.installed), period) {
+                entry.spent += component.price.unwrap_or(0.0);
+            }
+        }
+    }
+    for event in services {
+        let entry = bike_line(&mut lines, bikes, &event.bike);
+        if in_period(Some(event.date), period) {
+            entry.spent += event.cost.unwrap_or(0.0);
+        }
+    }
+
+    let activities = activities_in(activities, period);
+    let mut lines: Vec<CostLine> = lines
+        .into_iter()
+        .map(|(gear_id, mut line)| {
+            line.distance_km = activities
+                .iter()
+                .filter(|a| a.gear_id.as_ref() == Some(&gear_id))
+                .map(|a| a.distance / 1000.0)
+                .sum();
+            line
+        })
+        .collect();
+    lines.sort_by(|a, b| a.label.cmp(&b.label));
+    lines
+}
+
+/// Cost per km of each kind of component: the prices of the components of
+/// that kind over the distance ridden on them
+pub fn category_costs(
+    components: &[Component],
+    events: &[ChainEvent],
+    activities: &[Activity],
+    period: Option<(NaiveDate, NaiveDate)>,
+) -> Vec<CostLine> {
+    let activities = activities_in(activities, period);
+    let mut lines: BTreeMap<&str, CostLine> = BTreeMap::new();
+    for component in components {
+        let entry = lines
+            .entry(component.kind.as_str())
+            .or_insert_with(|| CostLine {
+                label: component.kind.clone(),
+                ..Default::default()
+            });
+        if in_period(Some(component.installed), period) {
+            entry.spent += component.price.unwrap_or(0.0);
+        }
+        entry.distance_km += chain::ridden_km(component, events, &activities);
+    }
+    lines.into_values().collect()
+}
+
+fn print_lines(title: &str, lines: &[CostLine]) {
+    println!("{}", title.bright_cyan().bold());
+    if lines.is_empty() {
+        println!("  {}", "Nothing configured".dimmed());
+        return;
+    }
+
+    let width = lines
+        .iter()
+        .map(|line| line.label.chars().count())
+        .max()
+        .unwrap_or(0);
+    for line in lines {
+        let per_km = match line.per_km() {
+            Some(per_km) => format!("{per_km:.3}/km").bright_green().bold(),
+            None => "no distance".dimmed(),
+        };
+        println!(
+            "  {:<width$}  {:>10}  {:>10}  {}",
+            line.label.bright_white(),
+            format!("{:.2}", line.spent),
+            format!("{:.0} km", line.distance_km),
+            per_km
+        );
+    }
+}
+
+pub fn handle_cost(year: Option<i32>) -> Result<()> {
+    let config = Config::load()?;
+    let services = service::load_events()?;
+    let cache = ActivityCache::load()?;
+    if cache.activities.is_empty() {
+        eprintln!(
+            "{}",
+            "⚠️  No cached activities, so distances are zero. Run 'chain-life sync' first."
+                .yellow()
+        );
+    }
+
+    let period = year
+        .map(|year| crate::goal::goal_period(Some(year), None, None))
+        .transpose()?;
+    match year {
+        Some(year) => println!(
+            "{} {}",
+            "💰 Spending and distance in".bright_cyan().bold(),
+            year.to_string().bright_white().bold()
+        ),
+        None => println!("{}", "💰 Running cost per km".bright_cyan().bold()),
+    }
+    println!();
+
+    let bikes = bike_costs(
+        &config.bikes,
+        &config.components,
+        &services,
+        &cache.activities,
+        period,
+    );
+    print_lines("🚲 Bikes (purchase, parts and services)", &bikes);
+    println!();
+    let categories = category_costs(
+        &config.components,
+        &chain::load_chain_events()?,
+        &cache.activities,
+        period,
+    );
+    print_lines("🔩 Components by kind", &categories);
+
+    let total = CostLine {
+        label: "Total".to_string(),
+        spent: bikes.iter().map(|line| line.spent).sum(),
+        distance_km: bikes.iter().map(|line| line.distance_km).sum(),
+    };
+    println!();
+    print_lines("🧾 All bikes", &[total]);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{activity, chain, day, fitted_to, on_gear};
+
+    fn component(kind: &str, installed: &str, price: f64, bike: &str) -> Component {
+        Component {
+            name: format!("{kind} on {bike}"),
+            kind: kind.to_string(),
+            price: Some(price),
+            ..fitted_to(chain(installed, 3000.0), bike)
+        }
+    }
+
+    fn fixtures() -> (Vec<Bike>, Vec<Component>, Vec<ServiceEvent>, Vec<Activity>) {
+        let bikes = vec![Bike {
+            gear_id: "b1".to_string(),
+            name: Some("Canyon".to_string()),
+            price: Some(1000.0),
+            purchased: Some(day("2023-01-01")),
+        }];
+        let components = vec![
+            component("chain", "2024-01-01", 30.0, "b1"),
+            component("chain", "2024-01-01", 40.0, "b2"),
+            component("tyre", "2023-06-01", 50.0, "b1"),
+        ];
+        let services = vec![ServiceEvent {
+            id: 1,
+            date: day("2024-03-01"),
+            bike: "b1".to_string(),
+            kind: "brake bleed".to_string(),
+            cost: Some(20.0),
+            notes: None,
+        }];
+        let activities = vec![
+            on_gear(activity(1, "2023-07-01", 500.0), "b1"),
+            on_gear(activity(2, "2024-02-01", 500.0), "b1"),
+            on_gear(activity(3, "2024-02-02", 200.0), "b2"),
+        ];
+        (bikes, components, services, activities)
+    }
+
+    #[test]
+    fn test_running_bike_costs() {
+        let (bikes, components, services, activities) = fixtures();
+        let lines = bike_costs(&bikes, &components, &services, &activities, None);
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].label, "Canyon");
+        assert_eq!(lines[0].spent, 1100.0);
+        assert_eq!(lines[0].distance_km, 1000.0);
+        assert_eq!(lines[0].per_km(), Some(1.1));
+        assert_eq!(lines[1].label, "b2");
+        assert_eq!(lines[1].per_km(), Some(0.2));
+    }
+
+    #[test]
+    fn test_yearly_bike_costs() {
+        let (bikes, components, services, activities) = fixtures();
+        let year = Some((day("2024-01-01"), day("2024-12-31")));
+        let lines = bike_costs(&bikes, &components, &services, &activities, year);
+
+        // Only the chain and the service were paid for in 2024
+        assert_eq!(lines[0].spent, 50.0);
+        assert_eq!(lines[0].distance_km, 500.0);
+    }
+
+    #[test]
+    fn test_category_costs() {
+        let (_, components, _, activities) = fixtures();
+        let lines = category_costs(&components, &[], &activities, None);
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].label, "chain");
+        assert_eq!(lines[0].spent, 70.0);
+        assert_eq!(lines[0].distance_km, 700.0);
+        assert_eq!(lines[0].per_km(), Some(0.1));
+        assert_eq!(lines[1].label, "tyre");
+        assert_eq!(lines[1].distance_km, 1000.0);
+    }
+
+    #[test]
+    fn test_no_distance_has_no_cost_per_km() {
+        let line = CostLine {
+            label: "b3".to_string(),
+            spent: 10.0,
+            distance_km: 0.0,
+        };
+        assert_eq!(line.per_km(), None);
+    }
+}
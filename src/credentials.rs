@@ -0,0 +1,506 @@
+use crate::store;
+use crate::vault::{self, Location};
+use anyhow::{Context, Result};
+use chrono::Utc;
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::PathBuf;
+
+/// Seconds before expiry at which an access token is refreshed anyway
+const EXPIRY_MARGIN: i64 = 60;
+
+/// Scopes `auth` asks for unless told otherwise: enough for every command
+pub const DEFAULT_SCOPE: &str = "read,activity:read_all,profile:read_all,activity:write";
+
+/// Needed to read private activities as well as public ones
+pub const READ_ALL_ACTIVITIES: &str = "activity:read_all";
+/// Needed to list the athlete's bikes and shoes
+pub const READ_PROFILE: &str = "profile:read_all";
+/// Needed to change activities, e.g. their gear
+pub const WRITE_ACTIVITIES: &str = "activity:write";
+
+/// OAuth credentials saved by `auth` so other commands can call the API
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Credentials {
+    pub client_id: String,
+    pub client_secret: String,
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_at: i64,
+    pub athlete_id: i64,
+    pub athlete_name: Option<String>,
+    /// Scopes the athlete granted, unknown for credentials saved before they were recorded
+    #[serde(default)]
+    pub scopes: Option<Vec<String>>,
+}
+
+/// Data file with the [`Profile`] of the saved credentials
+const PROFILE_FILE: &str = "athlete";
+
+/// The non-secret part of the saved credentials, kept in a plain data file so
+/// reading the athlete doesn't have to unlock the keyring or encrypted file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub athlete_id: i64,
+    pub athlete_name: Option<String>,
+    #[serde(default)]
+    pub scopes: Option<Vec<String>>,
+}
+
+impl Profile {
+    /// Load the profile saved along with the credentials, if any
+    pub fn load() -> Result<Option<Self>> {
+        store::load(PROFILE_FILE)
+    }
+}
+
+impl From<&Credentials> for Profile {
+    fn from(credentials: &Credentials) -> Self {
+        Profile {
+            athlete_id: credentials.athlete_id,
+            athlete_name: credentials.athlete_name.clone(),
+            scopes: credentials.scopes.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct RefreshResponse {
+    access_token: String,
+    refresh_token: String,
+    expires_at: i64,
+}
+
+impl Credentials {
+    /// Load the saved credentials, if `auth` has been run
+    pub fn load() -> Result<Option<Self>> {
+        Ok(Self::load_with_location()?.map(|(credentials, _)| credentials))
+    }
+
+    /// Load the saved credentials and where they're kept. Credentials still
+    /// in the plaintext file of earlier versions are moved somewhere safer.
+    pub fn load_with_location() -> Result<Option<(Self, Location)>> {
+        let Some((secret, mut location)) = vault::load()? else {
+            return Ok(None);
+        };
+        let credentials: Self =
+            serde_json::from_str(&secret).context("Failed to parse the saved credentials")?;
+
+        if location == Location::PlaintextFile {
+            match credentials.save() {
+                Ok(saved) => {
+                    eprintln!(
+                        "{} {}",
+                        "🔒 Moved the plaintext credentials to".bright_cyan(),
+                        saved
+                    );
+                    location = saved;
+                }
+                Err(error) => eprintln!(
+                    "{} {error:#}",
+                    "⚠️  Credentials are still stored in plaintext:".yellow()
+                ),
+            }
+        } else if Profile::load()?.is_none() {
+            // Saved before the profile was kept separately
+            store::save(PROFILE_FILE, &Profile::from(&credentials))?;
+        }
+        Ok(Some((credentials, location)))
+    }
+
+    /// Save the credentials in the keyring or the encrypted file, returning
+    /// which, and their [`Profile`] in the data directory
+    pub fn save(&self) -> Result<Location> {
+        let location = vault::save(&serde_json::to_string(self)?)?;
+        store::save(PROFILE_FILE, &Profile::from(self))?;
+        Ok(location)
+    }
+
+    /// Remove the saved credentials and their profile
+    pub fn delete() -> Result<()> {
+        vault::delete()?;
+        store::remove(PROFILE_FILE)?;
+        Ok(())
+    }
+
+    /// Whether the access token has expired (or is about to) at Unix time `now`
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at - EXPIRY_MARGIN <= now
+    }
+
+    /// Fail with a hint to re-run `auth` if any of `required` wasn't granted.
+    /// Credentials with unknown scopes are let through.
+    pub fn require_scopes(&self, required: &[&str]) -> Result<()> {
+        let Some(granted) = &self.scopes else {
+            return Ok(());
+        };
+        let missing: Vec<&str> = required
+            .iter()
+            .copied()
+            .filter(|scope| !has_scope(granted, scope))
+            .collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        let mut wanted = granted.clone();
+        wanted.extend(missing.iter().map(|scope| scope.to_string()));
+        let (scopes, were, them) = match missing.len() {
+            1 => ("scope", "wasn't", "it"),
+            _ => ("scopes", "weren't", "them"),
+        };
+        Err(anyhow::anyhow!(
+            "This command needs the {} {scopes}, which {were} granted (granted: {}). \
+             Run 'chain-life auth --scope {}' to grant {them}",
+            missing.join(", "),
+            granted.join(","),
+            wanted.join(",")
+        ))
+    }
+
+    /// Exchange the refresh token for a new access token
+    pub async fn refresh(&mut self) -> Result<()> {
+        let params = [
+            ("client_id", self.client_id.as_str()),
+            ("client_secret", self.client_secret.as_str()),
+            ("refresh_token", self.refresh_token.as_str()),
+            ("grant_type", "refresh_token"),
+        ];
+
+        let response = reqwest::Client::new()
+            .post("https://www.strava.com/oauth/token")
+            .form(&params)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            return Err(anyhow::anyhow!("Token refresh failed: {}", error_text));
+        }
+
+        let refreshed: RefreshResponse = response.json().await?;
+        self.access_token = refreshed.access_token;
+        self.refresh_token = refreshed.refresh_token;
+        self.expires_at = refreshed.expires_at;
+        Ok(())
+    }
+}
+
+/// Revoke the application's access to the athlete's account. Returns false if
+/// Strava no longer accepted the token, i.e. access was already revoked.
+async fn deauthorize(access_token: &str) -> Result<bool> {
+    let response = reqwest::Client::new()
+        .post("https://www.strava.com/oauth/deauthorize")
+        .form(&[("access_token", access_token)])
+        .send()
+        .await?;
+
+    if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+        return Ok(false);
+    }
+    if !response.status().is_success() {
+        let error_text = response.text().await?;
+        return Err(anyhow::anyhow!("Deauthorization failed: {}", error_text));
+    }
+    Ok(true)
+}
+
+/// Rough length of a span of `seconds`, e.g. `2d 3h` or `5h 12m`
+fn humanize(seconds: i64) -> String {
+    let seconds = seconds.abs();
+    let (days, hours, minutes) = (
+        seconds / 86_400,
+        seconds % 86_400 / 3600,
+        seconds % 3600 / 60,
+    );
+    if days > 0 {
+        format!("{days}d {hours}h")
+    } else if hours > 0 {
+        format!("{hours}h {minutes}m")
+    } else if minutes > 0 {
+        format!("{minutes}m")
+    } else {
+        format!("{seconds}s")
+    }
+}
+
+/// When a token expiring at `expires_at` does so, relative to `now`
+fn expiry(expires_at: i64, now: i64) -> String {
+    if expires_at > now {
+        format!("in {}", humanize(expires_at - now))
+    } else {
+        format!("{} ago", humanize(now - expires_at))
+    }
+}
+
+/// Caches of the athlete's Strava data, removed on logout
+const ATHLETE_CACHES: [&str; 2] = ["activities", "gear"];
+
+pub async fn handle_logout() -> Result<()> {
+    match Credentials::load()? {
+        Some(mut credentials) => {
+            if credentials.is_expired(Utc::now().timestamp()) {
+                credentials.refresh().await.context(
+                    "Failed to refresh the token to revoke it. \
+                     Revoke access at https://www.strava.com/settings/apps instead",
+                )?;
+                // The old refresh token is void now, so a failed revoke can be retried
+                credentials.save()?;
+            }
+            if deauthorize(&credentials.access_token).await? {
+                println!(
+                    "{} {}",
+                    "🔓 Revoked chain-life's access for".bright_green().bold(),
+                    credentials
+                        .athlete_name
+                        .unwrap_or_else(|| format!("athlete {}", credentials.athlete_id))
+                        .bright_white()
+                        .bold()
+                );
+            } else {
+                println!("{}", "Access was already revoked".yellow());
+            }
+            Credentials::delete()?;
+            println!("{}", "🗑️ Removed saved credentials".bright_green());
+        }
+        None => {
+            println!("{}", "Not logged in".yellow());
+            return Ok(());
+        }
+    }
+
+    for name in ATHLETE_CACHES {
+        if store::remove(name)? {
+            println!("{} {}", "🗑️ Removed cached".bright_green(), name);
+        }
+    }
+    Ok(())
+}
+
+pub fn handle_whoami() -> Result<()> {
+    let (credentials, location) =
+        Credentials::load_with_location()?.context("Not logged in. Run 'chain-life auth' first")?;
+
+    let name = credentials.athlete_name.as_deref().unwrap_or("(no name)");
+    println!(
+        "{} {} {}",
+        "🏃 Athlete:".bright_cyan().bold(),
+        name.bright_white().bold(),
+        format!("(#{})", credentials.athlete_id).dimmed()
+    );
+
+    let scopes = match &credentials.scopes {
+        Some(scopes) => scopes.join(",").bright_white(),
+        None => "unknown, run 'chain-life auth' again to record them".yellow(),
+    };
+    println!("{} {}", "🔐 Scopes:".bright_cyan().bold(), scopes);
+
+    let now = Utc::now().timestamp();
+    let expires_at = chrono::DateTime::from_timestamp(credentials.expires_at, 0)
+        .context("Invalid token expiry")?
+        .with_timezone(&chrono::Local)
+        .format("%Y-%m-%d %H:%M");
+    let status = if credentials.expires_at <= now {
+        format!(
+            "expired {}, refreshed on next use",
+            expiry(credentials.expires_at, now)
+        )
+        .yellow()
+    } else {
+        expiry(credentials.expires_at, now).bright_green()
+    };
+    println!(
+        "{} {} ({})",
+        "⏰ Token expires:".bright_cyan().bold(),
+        expires_at.to_string().bright_white(),
+        status
+    );
+    println!("{} {}", "💾 Stored in:".bright_cyan().bold(), location);
+    Ok(())
+}
+
+pub fn handle_export(out: Option<PathBuf>, remove: bool) -> Result<()> {
+    let credentials = Credentials::load()?.context("Not logged in. Run 'chain-life auth' first")?;
+    let bundle = vault::seal(&serde_json::to_string(&credentials)?)?;
+
+    // Messages go to stderr so the bundle can be piped from stdout
+    match &out {
+        Some(path) => {
+            store::write_private_file(path, bundle.as_bytes())?;
+            eprintln!(
+                "{} {}",
+                "📦 Credentials exported to".bright_green().bold(),
+                path.display().to_string().bright_white()
+            );
+        }
+        None => print!("{bundle}"),
+    }
+
+    if remove {
+        Credentials::delete()?;
+        eprintln!(
+            "{}",
+            "🗑️ Removed the credentials from this machine".bright_green()
+        );
+    } else {
+        eprintln!(
+            "{}",
+            "⚠️  Stop using them here: once either machine refreshes the token, \
+             the other's refresh token stops working"
+                .yellow()
+        );
+    }
+    Ok(())
+}
+
+pub fn handle_import(file: PathBuf) -> Result<()> {
+    let bundle = if file.as_os_str() == "-" {
+        let mut bundle = Vec::new();
+        std::io::stdin().read_to_end(&mut bundle)?;
+        bundle
+    } else {
+        std::fs::read(&file).with_context(|| format!("Failed to read {}", file.display()))?
+    };
+    let credentials: Credentials = serde_json::from_str(&vault::unseal(&bundle)?)
+        .context("The bundle doesn't hold chain-life credentials")?;
+
+    let location = credentials.save()?;
+    println!(
+        "{} {} {} {}",
+        "📥 Imported credentials for".bright_green().bold(),
+        credentials
+            .athlete_name
+            .unwrap_or_else(|| format!("athlete {}", credentials.athlete_id))
+            .bright_white()
+            .bold(),
+        "into".bright_green().bold(),
+        location.to_string().bright_white()
+    );
+    Ok(())
+}
+
+/// Show only the start of a token or authorization code, for verbose output
+pub fn redact(secret: &str) -> String {
+    let shown = if secret.chars().count() > 8 { 4 } else { 0 };
+    let start: String = secret.chars().take(shown).collect();
+    format!("{start}… (redacted)")
+}
+
+/// Split a comma-separated scope list, as in the `scope` parameter of the redirect
+pub fn parse_scopes(scope: &str) -> Vec<String> {
+    scope
+        .split(',')
+        .map(str::trim)
+        .filter(|scope| !scope.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Whether `granted` covers `required`; a `_all` scope covers its narrower
+/// form, e.g. `activity:read_all` covers `activity:read`
+pub fn has_scope(granted: &[String], required: &str) -> bool {
+    granted
+        .iter()
+        .any(|scope| scope == required || scope.strip_suffix("_all") == Some(required))
+}
+
+/// Resolve the access token to use: `explicit` if given, otherwise the saved
+/// credentials, refreshing and re-saving them if the token has expired. The
+/// saved credentials must have been granted the `required` scopes; an explicit
+/// token is used as is, since its scopes are unknown.
+pub async fn access_token(explicit: Option<String>, required: &[&str]) -> Result<String> {
+    if let Some(token) = explicit {
+        return Ok(token);
+    }
+
+    let mut credentials = Credentials::load()?
+        .context("No saved credentials. Run 'chain-life auth' first or pass --token")?;
+    credentials.require_scopes(required)?;
+    if credentials.is_expired(Utc::now().timestamp()) {
+        credentials.refresh().await?;
+        credentials.save()?;
+    }
+    Ok(credentials.access_token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn credentials(scopes: Option<&str>) -> Credentials {
+        Credentials {
+            client_id: "12345".to_string(),
+            client_secret: "secret".to_string(),
+            access_token: "access".to_string(),
+            refresh_token: "refresh".to_string(),
+            expires_at: 1_000,
+            athlete_id: 1,
+            athlete_name: None,
+            scopes: scopes.map(parse_scopes),
+        }
+    }
+
+    #[test]
+    fn test_profile_leaves_out_secrets() {
+        let profile = Profile::from(&credentials(Some("read")));
+        assert_eq!(profile.athlete_id, 1);
+        assert_eq!(profile.scopes, Some(vec!["read".to_string()]));
+
+        let json = serde_json::to_string(&profile).unwrap();
+        assert!(!json.contains("secret"));
+        assert!(!json.contains("access"));
+        assert!(!json.contains("refresh"));
+    }
+
+    #[test]
+    fn test_is_expired_with_margin() {
+        let credentials = credentials(None);
+        assert!(!credentials.is_expired(900));
+        assert!(credentials.is_expired(950));
+        assert!(credentials.is_expired(2_000));
+    }
+
+    #[test]
+    fn test_expiry() {
+        assert_eq!(expiry(1_000 + 5 * 3600 + 12 * 60, 1_000), "in 5h 12m");
+        assert_eq!(expiry(1_000 + 2 * 86_400 + 3 * 3600, 1_000), "in 2d 3h");
+        assert_eq!(expiry(1_000, 1_000 + 7 * 60 + 5), "7m ago");
+        assert_eq!(expiry(1_000, 1_030), "30s ago");
+    }
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(redact("abcdef123456"), "abcd… (redacted)");
+        assert_eq!(redact("abcd"), "… (redacted)");
+    }
+
+    #[test]
+    fn test_has_scope() {
+        let granted = parse_scopes("read, activity:read_all");
+        assert_eq!(granted, vec!["read", "activity:read_all"]);
+        assert!(has_scope(&granted, "read"));
+        assert!(has_scope(&granted, "activity:read"));
+        assert!(has_scope(&granted, "activity:read_all"));
+        assert!(!has_scope(&granted, "activity:write"));
+        assert!(!has_scope(&granted, "profile:read_all"));
+    }
+
+    #[test]
+    fn test_require_scopes() {
+        let read_only = credentials(Some("read,activity:read_all"));
+        assert!(read_only.require_scopes(&[READ_ALL_ACTIVITIES]).is_ok());
+
+        let error = read_only
+            .require_scopes(&[READ_ALL_ACTIVITIES, WRITE_ACTIVITIES])
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("needs the activity:write scope"));
+        assert!(error.contains("auth --scope read,activity:read_all,activity:write"));
+
+        // Credentials saved before scopes were recorded aren't second-guessed
+        assert!(credentials(None)
+            .require_scopes(&[WRITE_ACTIVITIES])
+            .is_ok());
+    }
+}
@@ -0,0 +1,505 @@
+use crate::activity::Totals;
+use crate::cache::{self, ActivityCache};
+use crate::chain::{self, ChainEvent};
+use crate::component::Component;
+use crate::config::Config;
+use crate::credentials;
+use crate::goal::{self, Goal, GoalProgress};
+use crate::strava::Activity;
+use anyhow::Result;
+use chrono::{Datelike, Days, Months, NaiveDate, Utc};
+use colored::*;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Gauge, Paragraph, Row, Table};
+use ratatui::{DefaultTerminal, Frame};
+use std::time::Duration;
+
+/// Number of activities shown in the recent activities list
+const RECENT_ACTIVITIES: usize = 50;
+
+/// Activity types shown in the dashboard, switched with `t`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TypeGroup {
+    Cycling,
+    Running,
+    All,
+}
+
+impl TypeGroup {
+    fn next(self) -> Self {
+        match self {
+            TypeGroup::Cycling => TypeGroup::Running,
+            TypeGroup::Running => TypeGroup::All,
+            TypeGroup::All => TypeGroup::Cycling,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            TypeGroup::Cycling => "Cycling",
+            TypeGroup::Running => "Running",
+            TypeGroup::All => "All activities",
+        }
+    }
+
+    fn types(self) -> Vec<String> {
+        let input = match self {
+            TypeGroup::Cycling => "cycling",
+            TypeGroup::Running => "running",
+            TypeGroup::All => "all",
+        };
+        crate::parse_activity_types(input).unwrap_or_default()
+    }
+}
+
+/// Date range the totals and activity list cover, switched with `d`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DateRange {
+    ThisYear,
+    Last30Days,
+    Last12Months,
+    LastYear,
+}
+
+impl DateRange {
+    fn next(self) -> Self {
+        match self {
+            DateRange::ThisYear => DateRange::Last30Days,
+            DateRange::Last30Days => DateRange::Last12Months,
+            DateRange::Last12Months => DateRange::LastYear,
+            DateRange::LastYear => DateRange::ThisYear,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            DateRange::ThisYear => "This year",
+            DateRange::Last30Days => "Last 30 days",
+            DateRange::Last12Months => "Last 12 months",
+            DateRange::LastYear => "Last year",
+        }
+    }
+
+    /// First and last day of the range, both included
+    pub fn bounds(self, today: NaiveDate) -> (NaiveDate, NaiveDate) {
+        let year_start = |year| NaiveDate::from_ymd_opt(year, 1, 1).unwrap();
+        match self {
+            DateRange::ThisYear => (year_start(today.year()), today),
+            DateRange::Last30Days => (today - Days::new(29), today),
+            DateRange::Last12Months => (today - Months::new(12) + Days::new(1), today),
+            DateRange::LastYear => (
+                year_start(today.year() - 1),
+                NaiveDate::from_ymd_opt(today.year() - 1, 12, 31).unwrap(),
+            ),
+        }
+    }
+}
+
+struct Dashboard {
+    /// Resolved again on every refresh, since a saved token expires after six hours
+    token: Option<String>,
+    concurrency: usize,
+    activities: Vec<Activity>,
+    goals: Vec<Goal>,
+    components: Vec<Component>,
+    chain_events: Vec<ChainEvent>,
+    group: TypeGroup,
+    range: DateRange,
+    today: NaiveDate,
+    status: String,
+}
+
+impl Dashboard {
+    /// Earliest day any panel needs activities from
+    fn fetch_start(&self) -> NaiveDate {
+        let mut start = DateRange::LastYear.bounds(self.today).0;
+        start = start.min(DateRange::Last12Months.bounds(self.today).0);
+        for goal in &self.goals {
+            start = start.min(goal.start);
+        }
+        for component in &self.components {
+            start = start.min(component.installed);
+        }
+        start
+    }
+
+    /// Show the cached activities any panel needs, newest first
+    fn show(&mut self, cache: &ActivityCache) {
+        let start = self.fetch_start();
+        let mut activities: Vec<Activity> = cache
+            .activities
+            .iter()
+            .filter(|a| a.date().is_some_and(|date| date >= start))
+            .cloned()
+            .collect();
+        activities.sort_by(|a, b| b.start_date.cmp(&a.start_date));
+        self.activities = activities;
+    }
+
+    /// Show the local cache, then sync it with Strava as 'sync' does and show
+    /// the result
+    async fn refresh(&mut self) -> Result<()> {
+        self.today = Utc::now().date_naive();
+        let mut cache = ActivityCache::load()?;
+        self.show(&cache);
+
+        let reconcile_days = cache::reconcile_days(None)?;
+        let token =
+            credentials::access_token(self.token.clone(), &[credentials::READ_ALL_ACTIVITIES])
+                .await?;
+        cache::sync(&mut cache, token, self.concurrency, reconcile_days, false).await?;
+        cache.save()?;
+        self.show(&cache);
+        self.status = format!("Updated {}", Utc::now().format("%H:%M:%S"));
+        Ok(())
+    }
+
+    /// Activities of the selected group within the selected range, newest first
+    fn selected(&self) -> Vec<&Activity> {
+        let types = self.group.types();
+        let (start, end) = self.range.bounds(self.today);
+        self.activities
+            .iter()
+            .filter(|a| types.contains(&a.activity_type))
+            .filter(|a| a.date().is_some_and(|date| date >= start && date <= end))
+            .collect()
+    }
+
+    fn goal_progress(&self, goal: &Goal) -> GoalProgress {
+        let types = crate::parse_activity_types(&goal.activity_types).unwrap_or_default();
+        let end = goal.end.min(self.today);
+        let done_km = Totals::of(
+            self.activities
+                .iter()
+                .filter(|a| types.contains(&a.activity_type))
+                .filter(|a| {
+                    a.date()
+                        .is_some_and(|date| date >= goal.start && date <= end)
+                }),
+        )
+        .distance_km;
+        GoalProgress::compute(goal, done_km, self.today)
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [header, body, recent, footer] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(self.panel_height()),
+            Constraint::Min(5),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let (start, end) = self.range.bounds(self.today);
+        frame.render_widget(
+            Line::from(format!(
+                " 🚴 chain-life · {} · {} ({start} to {end})",
+                self.group.label(),
+                self.range.label()
+            ))
+            .style(Style::new().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+            header,
+        );
+
+        let [totals, goals, components] = Layout::horizontal([
+            Constraint::Length(30),
+            Constraint::Fill(1),
+            Constraint::Fill(1),
+        ])
+        .areas(body);
+        let selected = self.selected();
+        self.draw_totals(frame, totals, &selected);
+        self.draw_goals(frame, goals);
+        self.draw_components(frame, components);
+        self.draw_recent(frame, recent, &selected);
+
+        frame.render_widget(
+            Line::from(format!(
+                " t: activity types · d: date range · r: refresh · q: quit   {}",
+                self.status
+            ))
+            .style(Style::new().fg(Color::DarkGray)),
+            footer,
+        );
+    }
+
+    fn panel_height(&self) -> u16 {
+        let rows = self.goals.len().max(self.components.len()).max(4);
+        rows as u16 + 2
+    }
+
+    fn draw_totals(&self, frame: &mut Frame, area: Rect, selected: &[&Activity]) {
+        let totals = Totals::of(selected.iter().copied());
+        let lines = vec![
+            Line::from(format!("Distance:  {:.1} km", totals.distance_km))
+                .style(Style::new().fg(Color::Green).add_modifier(Modifier::BOLD)),
+            Line::from(format!("Activities: {}", totals.count)),
+            Line::from(format!("Moving:    {:.1} h", totals.moving_hours)),
+            Line::from(format!("Elevation: {:.0} m", totals.elevation_m)),
+        ];
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(" Totals ")),
+            area,
+        );
+    }
+
+    fn draw_goals(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::bordered().title(" Goals ");
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        if self.goals.is_empty() {
+            frame.render_widget(
+                Line::from("No goals set").style(Style::new().fg(Color::DarkGray)),
+                inner,
+            );
+            return;
+        }
+
+        for (goal, row) in self.goals.iter().zip(rows(inner)) {
+            let progress = self.goal_progress(goal);
+            let ratio = progress.done_km / goal.distance_km;
+            let color = if progress.projected_km >= goal.distance_km {
+                Color::Green
+            } else {
+                Color::Yellow
+            };
+            frame.render_widget(
+                gauge(
+                    ratio,
+                    color,
+                    format!(
+                        "{}: {:.0} / {:.0} km ({:.0}%)",
+                        goal.name,
+                        progress.done_km,
+                        goal.distance_km,
+                        ratio * 100.0
+                    ),
+                ),
+                row,
+            );
+        }
+    }
+
+    fn draw_components(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::bordered().title(" Component wear ");
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        if self.components.is_empty() {
+            frame.render_widget(
+                Line::from("No components configured").style(Style::new().fg(Color::DarkGray)),
+                inner,
+            );
+            return;
+        }
+
+        for (component, row) in self.components.iter().zip(rows(inner)) {
+            let distance_km = chain::ridden_km(component, &self.chain_events, &self.activities);
+            let wear = component.wear_at(distance_km);
+            let color = if wear >= 1.0 {
+                Color::Red
+            } else if wear >= 0.8 {
+                Color::Yellow
+            } else {
+                Color::Green
+            };
+            frame.render_widget(
+                gauge(
+                    wear,
+                    color,
+                    format!(
+                        "{} ({}): {:.0} / {:.0} km",
+                        component.name, component.kind, distance_km, component.rated_km
+                    ),
+                ),
+                row,
+            );
+        }
+    }
+
+    fn draw_recent(&self, frame: &mut Frame, area: Rect, selected: &[&Activity]) {
+        let rows = selected.iter().take(RECENT_ACTIVITIES).map(|activity| {
+            Row::new(vec![
+                activity
+                    .start_date
+                    .get(..10)
+                    .unwrap_or_default()
+                    .to_string(),
+                activity.name.clone(),
+                activity.activity_type.clone(),
+                format!("{:>7.1} km", activity.distance / 1000.0),
+                format!("{:>5.0} m", activity.total_elevation_gain),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(10),
+                Constraint::Fill(1),
+                Constraint::Length(16),
+                Constraint::Length(10),
+                Constraint::Length(7),
+            ],
+        )
+        .header(
+            Row::new(vec!["Date", "Name", "Type", "Distance", "Climb"])
+                .style(Style::new().add_modifier(Modifier::BOLD)),
+        )
+        .block(Block::bordered().title(" Recent activities "));
+        frame.render_widget(table, area);
+    }
+}
+
+/// Split `area` into one-line rows
+fn rows(area: Rect) -> impl Iterator<Item = Rect> {
+    (0..area.height).map(move |i| Rect {
+        y: area.y + i,
+        height: 1,
+        ..area
+    })
+}
+
+fn gauge(ratio: f64, color: Color, label: String) -> Gauge<'static> {
+    Gauge::default()
+        .gauge_style(Style::new().fg(color).bg(Color::DarkGray))
+        .ratio(ratio.clamp(0.0, 1.0))
+        .label(label)
+}
+
+pub async fn handle_dashboard(token: Option<String>, concurrency: usize) -> Result<()> {
+    let config = Config::load()?;
+    let mut dashboard = Dashboard {
+        token,
+        concurrency,
+        activities: Vec::new(),
+        goals: goal::load_goals()?,
+        components: config.components,
+        chain_events: chain::load_chain_events()?,
+        group: TypeGroup::Cycling,
+        range: DateRange::ThisYear,
+        today: Utc::now().date_naive(),
+        status: String::new(),
+    };
+
+    println!("{}", "📡 Syncing activities...".cyan());
+    dashboard.refresh().await?;
+
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, &mut dashboard).await;
+    ratatui::restore();
+    result
+}
+
+async fn run(terminal: &mut DefaultTerminal, dashboard: &mut Dashboard) -> Result<()> {
+    loop {
+        terminal.draw(|frame| dashboard.draw(frame))?;
+
+        if !event::poll(Duration::from_millis(250))? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Char('t') => dashboard.group = dashboard.group.next(),
+            KeyCode::Char('d') => dashboard.range = dashboard.range.next(),
+            KeyCode::Char('r') => {
+                dashboard.status = "Refreshing...".to_string();
+                terminal.draw(|frame| dashboard.draw(frame))?;
+                if let Err(err) = dashboard.refresh().await {
+                    dashboard.status = format!("Refresh failed: {err}");
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{activity, of_type};
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_date_range_bounds() {
+        let today = date(2025, 3, 15);
+        assert_eq!(DateRange::ThisYear.bounds(today), (date(2025, 1, 1), today));
+        assert_eq!(
+            DateRange::Last30Days.bounds(today),
+            (date(2025, 2, 14), today)
+        );
+        assert_eq!(
+            DateRange::Last12Months.bounds(today),
+            (date(2024, 3, 16), today)
+        );
+        assert_eq!(
+            DateRange::LastYear.bounds(today),
+            (date(2024, 1, 1), date(2024, 12, 31))
+        );
+    }
+
+    #[test]
+    fn test_show_and_draw_selected_totals() {
+        let today = date(2025, 3, 15);
+        let mut dashboard = Dashboard {
+            token: None,
+            concurrency: 1,
+            activities: Vec::new(),
+            goals: Vec::new(),
+            components: Vec::new(),
+            chain_events: Vec::new(),
+            group: TypeGroup::Cycling,
+            range: DateRange::ThisYear,
+            today,
+            status: String::new(),
+        };
+        // The cache is oldest first and goes back further than any panel needs
+        dashboard.show(&ActivityCache {
+            synced_at: None,
+            activities: vec![
+                activity(0, "2023-06-01", 20.0),
+                activity(1, "2024-12-31", 100.0),
+                of_type(activity(2, "2025-02-01", 10.0), "Run"),
+                activity(3, "2025-03-10", 42.0),
+            ],
+        });
+        let ids: Vec<i64> = dashboard.activities.iter().map(|a| a.id).collect();
+        assert_eq!(ids, vec![3, 2, 1]);
+
+        let mut terminal =
+            ratatui::Terminal::new(ratatui::backend::TestBackend::new(100, 20)).unwrap();
+        terminal.draw(|frame| dashboard.draw(frame)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+
+        assert!(screen.contains("Distance:  42.0 km"));
+        assert!(screen.contains("Activity 3"));
+        assert!(!screen.contains("Activity 2"));
+        assert!(screen.contains("No goals set"));
+    }
+
+    #[test]
+    fn test_selection_cycles() {
+        assert_eq!(TypeGroup::All.next(), TypeGroup::Cycling);
+        assert_eq!(DateRange::LastYear.next(), DateRange::ThisYear);
+        assert!(TypeGroup::Running.types().contains(&"TrailRun".to_string()));
+    }
+}
@@ -0,0 +1,58 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::path::Path;
+
+/// File formats supported when exporting data for further analysis
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+/// Quote a CSV field if it contains a separator, quote or newline
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Render a header and rows as CSV text
+pub fn to_csv(header: &[String], rows: &[Vec<String>]) -> String {
+    let mut out = String::new();
+    for line in std::iter::once(header).chain(rows.iter().map(Vec::as_slice)) {
+        let fields: Vec<String> = line.iter().map(|field| csv_field(field)).collect();
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+/// Write exported content to `path`
+pub fn write_file(path: &Path, content: &str) -> Result<()> {
+    std::fs::write(path, content)
+        .with_context(|| format!("Failed to write export file {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_field_quotes_when_needed() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let header = vec!["time".to_string(), "watts".to_string()];
+        let rows = vec![
+            vec!["0".to_string(), "250".to_string()],
+            vec!["1".to_string(), String::new()],
+        ];
+        assert_eq!(to_csv(&header, &rows), "time,watts\n0,250\n1,\n");
+    }
+}
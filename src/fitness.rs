@@ -0,0 +1,362 @@
+use crate::config::Config;
+use crate::credentials;
+use crate::export::{self, ExportFormat};
+use crate::strava::{self, Activity, ActivityQuery};
+use anyhow::{Context, Result};
+use chrono::{Days, NaiveDate, Utc};
+use colored::*;
+use futures::TryStreamExt;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Time constant of chronic training load ("fitness"), in days
+const CTL_DAYS: f64 = 42.0;
+
+/// Time constant of acute training load ("fatigue"), in days
+const ATL_DAYS: f64 = 7.0;
+
+/// Days of history fetched before the requested range so the rolling loads
+/// have settled by the time the range starts
+const WARMUP_DAYS: u64 = 90;
+
+/// What a training stress score was derived from
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StressSource {
+    Power,
+    HeartRate,
+}
+
+/// Training stress of a single activity
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ActivityStress {
+    pub id: i64,
+    pub date: NaiveDate,
+    pub name: String,
+    pub activity_type: String,
+    pub source: StressSource,
+    pub intensity: f64,
+    pub tss: f64,
+}
+
+/// Training load at the end of a single day
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DailyLoad {
+    pub date: NaiveDate,
+    pub tss: f64,
+    /// Chronic training load ("fitness")
+    pub ctl: f64,
+    /// Acute training load ("fatigue")
+    pub atl: f64,
+    /// Training stress balance ("form"): yesterday's fitness minus fatigue
+    pub tsb: f64,
+}
+
+/// Compute training stress for an activity, preferring power over heart rate.
+///
+/// Power-based TSS uses weighted average power as a stand-in for normalized
+/// power; heart rate-based TSS uses average heart rate relative to threshold.
+pub fn training_stress(
+    activity: &Activity,
+    ftp: Option<f64>,
+    threshold_hr: Option<f64>,
+) -> Option<(StressSource, f64, f64)> {
+    let hours = activity.moving_time as f64 / 3600.0;
+
+    let power = activity.weighted_average_watts.or(activity.average_watts);
+    if let (Some(power), Some(ftp)) = (power, ftp.filter(|ftp| *ftp > 0.0)) {
+        let intensity = power / ftp;
+        return Some((
+            StressSource::Power,
+            intensity,
+            hours * intensity.powi(2) * 100.0,
+        ));
+    }
+
+    if let (Some(hr), Some(threshold)) = (
+        activity.average_heartrate,
+        threshold_hr.filter(|t| *t > 0.0),
+    ) {
+        let intensity = hr / threshold;
+        return Some((
+            StressSource::HeartRate,
+            intensity,
+            hours * intensity.powi(2) * 100.0,
+        ));
+    }
+
+    None
+}
+
+/// Roll daily stress into exponentially weighted chronic and acute loads,
+/// starting from zero on `start` and reporting every day up to `end`
+pub fn training_load(
+    daily_tss: &BTreeMap<NaiveDate, f64>,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> Vec<DailyLoad> {
+    let mut loads = Vec::new();
+    let (mut ctl, mut atl) = (0.0, 0.0);
+
+    for date in start.iter_days().take_while(|date| *date <= end) {
+        let tss = daily_tss.get(&date).copied().unwrap_or(0.0);
+        let tsb = ctl - atl;
+        ctl += (tss - ctl) / CTL_DAYS;
+        atl += (tss - atl) / ATL_DAYS;
+        loads.push(DailyLoad {
+            date,
+            tss,
+            ctl,
+            atl,
+            tsb,
+        });
+    }
+
+    loads
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_fitness(
+    from: String,
+    to: Option<String>,
+    token: Option<String>,
+    activity_types: String,
+    concurrency: usize,
+    ftp: Option<f64>,
+    threshold_hr: Option<f64>,
+    export: Option<PathBuf>,
+    format: ExportFormat,
+    verbose: bool,
+) -> Result<()> {
+    let config = Config::load()?;
+    let ftp = ftp.or(config.ftp);
+    let threshold_hr = threshold_hr.or(config.threshold_hr);
+    if ftp.is_none() && threshold_hr.is_none() {
+        return Err(anyhow::anyhow!(
+            "No FTP or threshold heart rate configured. Pass --ftp/--threshold-hr or set them in {}",
+            crate::config::config_path()?.display()
+        ));
+    }
+
+    let from = crate::parse_date(&from).context("Failed to parse the provided start date")?;
+    let to = match to {
+        Some(to) => crate::parse_date(&to).context("Failed to parse the provided end date")?,
+        None => Utc::now().date_naive(),
+    };
+    if to < from {
+        return Err(anyhow::anyhow!(
+            "End date must not be before the start date"
+        ));
+    }
+    let allowed_types = crate::parse_activity_types(&activity_types)?;
+
+    let warmup_start = from - Days::new(WARMUP_DAYS);
+    if verbose {
+        println!(
+            "{} {} {} {}",
+            "📡 Fetching activities from".cyan(),
+            warmup_start.to_string().bright_white(),
+            "to".cyan(),
+            to.to_string().bright_white()
+        );
+    }
+
+    let query = ActivityQuery {
+        after: Some(strava::day_timestamp(warmup_start)),
+        before: Some(strava::day_timestamp(to + Days::new(1))),
+        concurrency,
+        verbose,
+    };
+    let token = credentials::access_token(token, &[credentials::READ_ALL_ACTIVITIES]).await?;
+    let activities: Vec<Activity> = strava::activities(reqwest::Client::new(), token, query)
+        .try_collect()
+        .await?;
+
+    let mut stresses = Vec::new();
+    let mut daily_tss = BTreeMap::new();
+    for activity in activities
+        .iter()
+        .filter(|a| allowed_types.contains(&a.activity_type))
+    {
+        let (Some(date), Some((source, intensity, tss))) = (
+            activity.date(),
+            training_stress(activity, ftp, threshold_hr),
+        ) else {
+            continue;
+        };
+        *daily_tss.entry(date).or_insert(0.0) += tss;
+        if date >= from {
+            stresses.push(ActivityStress {
+                id: activity.id,
+                date,
+                name: activity.name.clone(),
+                activity_type: activity.activity_type.clone(),
+                source,
+                intensity,
+                tss,
+            });
+        }
+    }
+    stresses.sort_by_key(|stress| stress.date);
+
+    let loads: Vec<DailyLoad> = training_load(&daily_tss, warmup_start, to)
+        .into_iter()
+        .filter(|load| load.date >= from)
+        .collect();
+
+    print_stresses(&stresses);
+    println!();
+    print_loads(&loads);
+
+    if let Some(path) = export {
+        let content = match format {
+            ExportFormat::Csv => {
+                let header = ["date", "tss", "ctl", "atl", "tsb"]
+                    .map(String::from)
+                    .to_vec();
+                let rows: Vec<Vec<String>> = loads
+                    .iter()
+                    .map(|load| {
+                        vec![
+                            load.date.to_string(),
+                            format!("{:.1}", load.tss),
+                            format!("{:.1}", load.ctl),
+                            format!("{:.1}", load.atl),
+                            format!("{:.1}", load.tsb),
+                        ]
+                    })
+                    .collect();
+                export::to_csv(&header, &rows)
+            }
+            ExportFormat::Json => serde_json::to_string_pretty(&serde_json::json!({
+                "activities": stresses,
+                "daily": loads,
+            }))?,
+        };
+        export::write_file(&path, &content)?;
+        println!();
+        println!(
+            "{} {}",
+            "💾 Training load exported to".bright_green().bold(),
+            path.display().to_string().bright_white().bold()
+        );
+    }
+
+    Ok(())
+}
+
+fn print_stresses(stresses: &[ActivityStress]) {
+    println!("{}", "🏋️ Training stress per activity".bright_cyan().bold());
+    if stresses.is_empty() {
+        println!(
+            "  {}",
+            "No activities with power or heart rate data in range".dimmed()
+        );
+        return;
+    }
+
+    println!(
+        "  {}",
+        format!(
+            "{:<10}  {:<6}  {:>5}  {:>6}  {}",
+            "Date", "Source", "IF", "TSS", "Activity"
+        )
+        .dimmed()
+    );
+    for stress in stresses {
+        let source = match stress.source {
+            StressSource::Power => "power",
+            StressSource::HeartRate => "hr",
+        };
+        println!(
+            "  {:<10}  {:<6}  {:>5.2}  {}  {} ({})",
+            stress.date.to_string().bright_white(),
+            source.bright_blue(),
+            stress.intensity,
+            format!("{:>6.1}", stress.tss).bright_green().bold(),
+            stress.name.bright_white(),
+            stress.activity_type.bright_blue()
+        );
+    }
+}
+
+fn print_loads(loads: &[DailyLoad]) {
+    println!("{}", "📈 Training load".bright_cyan().bold());
+    println!(
+        "  {}",
+        format!(
+            "{:<10}  {:>6}  {:>6}  {:>6}  {:>6}",
+            "Date", "TSS", "CTL", "ATL", "TSB"
+        )
+        .dimmed()
+    );
+    for load in loads {
+        let tsb = format!("{:>6.1}", load.tsb);
+        let tsb = if load.tsb >= 0.0 {
+            tsb.bright_green()
+        } else {
+            tsb.bright_red()
+        };
+        println!(
+            "  {:<10}  {:>6.1}  {}  {}  {}",
+            load.date.to_string().bright_white(),
+            load.tss,
+            format!("{:>6.1}", load.ctl).bright_cyan(),
+            format!("{:>6.1}", load.atl).bright_magenta(),
+            tsb
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::activity;
+
+    fn effort(moving_time: i32, watts: Option<f64>, hr: Option<f64>) -> Activity {
+        Activity {
+            moving_time,
+            elapsed_time: moving_time,
+            average_watts: watts,
+            average_heartrate: hr,
+            ..activity(1, "2024-01-15", 30.0)
+        }
+    }
+
+    #[test]
+    fn test_one_hour_at_ftp_is_100_tss() {
+        let (source, intensity, tss) =
+            training_stress(&effort(3600, Some(250.0), None), Some(250.0), None).unwrap();
+        assert_eq!(source, StressSource::Power);
+        assert!((intensity - 1.0).abs() < 1e-9);
+        assert!((tss - 100.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_falls_back_to_heart_rate() {
+        let (source, _, tss) =
+            training_stress(&effort(7200, None, Some(150.0)), Some(250.0), Some(150.0)).unwrap();
+        assert_eq!(source, StressSource::HeartRate);
+        assert!((tss - 200.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_no_stress_without_thresholds() {
+        assert!(training_stress(&effort(3600, Some(250.0), Some(150.0)), None, None).is_none());
+    }
+
+    #[test]
+    fn test_training_load() {
+        let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let mut daily = BTreeMap::new();
+        daily.insert(start, 70.0);
+
+        let loads = training_load(&daily, start, start + Days::new(1));
+        assert_eq!(loads.len(), 2);
+        assert!((loads[0].ctl - 70.0 / 42.0).abs() < 1e-9);
+        assert!((loads[0].atl - 10.0).abs() < 1e-9);
+        assert_eq!(loads[0].tsb, 0.0);
+        assert!((loads[1].tsb - (loads[0].ctl - loads[0].atl)).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,446 @@
+use crate::cache::ActivityCache;
+use crate::credentials;
+use crate::store;
+use crate::strava::{self, Activity, Gear};
+use anyhow::{Context, Result};
+use colored::*;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Whether a piece of gear is a bike or a pair of shoes
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GearKind {
+    Bike,
+    Shoes,
+}
+
+/// An athlete's gear as cached by `gear list`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AthleteGear {
+    pub kind: GearKind,
+    pub primary: bool,
+    #[serde(flatten)]
+    pub gear: Gear,
+}
+
+impl AthleteGear {
+    /// Name to show for the gear, falling back to its ID
+    pub fn label(&self) -> &str {
+        self.gear.name.as_deref().unwrap_or(&self.gear.id)
+    }
+}
+
+pub fn load_gear() -> Result<Vec<AthleteGear>> {
+    store::load("gear")
+}
+
+/// Strava's distance for a piece of gear next to the distance of the cached
+/// activities assigned to it
+#[derive(Debug, Clone, PartialEq)]
+pub struct GearReconciliation {
+    pub id: String,
+    pub name: String,
+    pub strava_km: f64,
+    pub computed_km: f64,
+    pub activities: usize,
+}
+
+impl GearReconciliation {
+    pub fn difference_km(&self) -> f64 {
+        self.strava_km - self.computed_km
+    }
+}
+
+/// Compare each piece of gear's distance with the activities assigned to it.
+/// Also returns the number and distance of `allowed_types` activities without
+/// any gear.
+pub fn reconcile(
+    gear: &[AthleteGear],
+    activities: &[Activity],
+    allowed_types: &[String],
+) -> (Vec<GearReconciliation>, usize, f64) {
+    let lines = gear
+        .iter()
+        .map(|item| {
+            let assigned: Vec<&Activity> = activities
+                .iter()
+                .filter(|a| a.gear_id.as_deref() == Some(item.gear.id.as_str()))
+                .collect();
+            GearReconciliation {
+                id: item.gear.id.clone(),
+                name: item.label().to_string(),
+                strava_km: item.gear.distance / 1000.0,
+                computed_km: assigned.iter().map(|a| a.distance).sum::<f64>() / 1000.0,
+                activities: assigned.len(),
+            }
+        })
+        .collect();
+
+    let unassigned: Vec<&Activity> = activities
+        .iter()
+        .filter(|a| a.gear_id.is_none() && allowed_types.contains(&a.activity_type))
+        .collect();
+    let unassigned_km = unassigned.iter().map(|a| a.distance).sum::<f64>() / 1000.0;
+    (lines, unassigned.len(), unassigned_km)
+}
+
+pub async fn handle_list(token: Option<String>, verbose: bool) -> Result<()> {
+    let token = credentials::access_token(token, &[credentials::READ_PROFILE]).await?;
+    let client = reqwest::Client::new();
+    let athlete = strava::get_athlete(&client, &token).await?;
+
+    let listed = athlete
+        .bikes
+        .into_iter()
+        .map(|gear| (GearKind::Bike, gear))
+        .chain(
+            athlete
+                .shoes
+                .into_iter()
+                .map(|gear| (GearKind::Shoes, gear)),
+        );
+    let mut gear = Vec::new();
+    for (kind, summary) in listed {
+        if verbose {
+            println!(
+                "{} {}",
+                "📡 Fetching gear".cyan(),
+                summary.id.bright_white()
+            );
+        }
+        gear.push(AthleteGear {
+            kind,
+            primary: summary.primary,
+            gear: strava::get_gear(&client, &token, &summary.id).await?,
+        });
+    }
+    store::save("gear", &gear)?;
+
+    if gear.is_empty() {
+        println!("{}", "No gear on the Strava profile".yellow());
+        return Ok(());
+    }
+
+    println!("{}", "🚲 Gear:".bright_cyan().bold());
+    for item in &gear {
+        let icon = match item.kind {
+            GearKind::Bike => "🚲",
+            GearKind::Shoes => "👟",
+        };
+        let model = [
+            item.gear.brand_name.as_deref(),
+            item.gear.model_name.as_deref(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ");
+        let mut flags = Vec::new();
+        if item.primary {
+            flags.push("primary".bright_green().to_string());
+        }
+        if item.gear.retired {
+            flags.push("retired".dimmed().to_string());
+        }
+        println!(
+            "  {} {} {} {}  {}  {}",
+            icon,
+            item.gear.id.dimmed(),
+            item.label().bright_white().bold(),
+            model.bright_blue(),
+            format!("{:.0} km", item.gear.distance / 1000.0).bright_white(),
+            flags.join(" ")
+        );
+    }
+    Ok(())
+}
+
+pub fn handle_reconcile(activity_types: String) -> Result<()> {
+    let gear = load_gear()?;
+    if gear.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No cached gear. Run 'chain-life gear list' first"
+        ));
+    }
+    let allowed_types = crate::parse_activity_types(&activity_types)?;
+    let cache = ActivityCache::load()?;
+    let (lines, unassigned, unassigned_km) = reconcile(&gear, &cache.activities, &allowed_types);
+
+    println!(
+        "{}",
+        "🔍 Gear distance: Strava vs. cached activities"
+            .bright_cyan()
+            .bold()
+    );
+    for line in &lines {
+        let difference = line.difference_km();
+        let difference = if difference.abs() < 1.0 {
+            "matches".bright_green()
+        } else {
+            format!("{difference:+.0} km").bright_yellow().bold()
+        };
+        println!(
+            "  {} {}: {} on Strava, {} from {} activities  {}",
+            "•".bright_blue(),
+            line.name.bright_white().bold(),
+            format!("{:.0} km", line.strava_km).bright_white(),
+            format!("{:.0} km", line.computed_km).bright_white(),
+            line.activities,
+            difference
+        );
+    }
+
+    println!();
+    if unassigned > 0 {
+        println!(
+            "{} {} activities ({} km) have no gear assigned",
+            "⚠️ ".bright_yellow(),
+            unassigned.to_string().bright_yellow().bold(),
+            format!("{unassigned_km:.0}").bright_yellow().bold()
+        );
+    } else {
+        println!("{}", "✅ Every activity has gear assigned".bright_green());
+    }
+    println!(
+        "{}",
+        "A positive difference can also mean activities older than the cache, or gear distance entered by hand on Strava."
+            .dimmed()
+    );
+    Ok(())
+}
+
+/// Which activities `gear assign` moves to the new gear
+#[derive(Debug, Clone)]
+pub struct AssignFilter {
+    pub allowed_types: Vec<String>,
+    /// Pattern the activity name must match
+    pub name: Option<Regex>,
+    /// Only trainer (`Some(true)`) or only outdoor (`Some(false)`) activities
+    pub trainer: Option<bool>,
+}
+
+impl AssignFilter {
+    /// Whether `activity` matches and isn't on `gear_id` already
+    pub fn matches(&self, activity: &Activity, gear_id: &str) -> bool {
+        activity.gear_id.as_deref() != Some(gear_id)
+            && self.allowed_types.contains(&activity.activity_type)
+            && self
+                .name
+                .as_ref()
+                .is_none_or(|name| name.is_match(&activity.name))
+            && self
+                .trainer
+                .is_none_or(|trainer| activity.trainer == trainer)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_assign(
+    gear_id: String,
+    from: String,
+    to: Option<String>,
+    activity_types: String,
+    name: Option<String>,
+    trainer: Option<bool>,
+    dry_run: bool,
+    token: Option<String>,
+    concurrency: usize,
+    verbose: bool,
+) -> Result<()> {
+    let from = crate::parse_date(&from)?;
+    let to = to.as_deref().map(crate::parse_date).transpose()?;
+    let filter = AssignFilter {
+        allowed_types: crate::parse_activity_types(&activity_types)?,
+        name: name
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .context("Invalid --name pattern")?,
+        trainer,
+    };
+
+    let token = credentials::access_token(
+        token,
+        &[
+            credentials::READ_ALL_ACTIVITIES,
+            credentials::WRITE_ACTIVITIES,
+        ],
+    )
+    .await?;
+    let activities = crate::fetch_activities_since(
+        from,
+        to,
+        token.clone(),
+        filter.allowed_types.clone(),
+        concurrency,
+        verbose,
+    )
+    .await?;
+    let matching: Vec<&Activity> = activities
+        .iter()
+        .filter(|activity| filter.matches(activity, &gear_id))
+        .collect();
+
+    if matching.is_empty() {
+        println!("{}", "No matching activities to reassign".yellow());
+        return Ok(());
+    }
+
+    println!(
+        "{} {} activities to {}:",
+        if dry_run {
+            "🔎 Would assign"
+        } else {
+            "🚲 Assigning"
+        }
+        .bright_cyan()
+        .bold(),
+        matching.len().to_string().bright_white().bold(),
+        gear_id.bright_blue()
+    );
+    for activity in &matching {
+        println!(
+            "  {} {} {} ({}, {:.1} km) {} {}",
+            "•".bright_blue(),
+            activity.start_date.get(..10).unwrap_or_default(),
+            activity.name.bright_white(),
+            activity.activity_type,
+            activity.distance / 1000.0,
+            activity.gear_id.as_deref().unwrap_or("no gear").dimmed(),
+            format!("→ {gear_id}").bright_blue()
+        );
+    }
+    if dry_run {
+        println!();
+        println!("{}", "Dry run, nothing was changed".yellow());
+        return Ok(());
+    }
+
+    let client = reqwest::Client::new();
+    let mut updated = Vec::new();
+    let mut failure = None;
+    for activity in &matching {
+        match strava::update_activity_gear(&client, &token, activity.id, &gear_id).await {
+            Ok(activity) => updated.push(activity),
+            Err(error) => {
+                failure = Some(error.context(format!("Failed to update '{}'", activity.name)));
+                break;
+            }
+        }
+    }
+
+    // Keep cached copies in line so totals and wear reflect the new gear
+    let mut cache = ActivityCache::load()?;
+    let mut cached = 0;
+    for activity in &updated {
+        if let Some(existing) = cache.activities.iter_mut().find(|a| a.id == activity.id) {
+            *existing = activity.clone();
+            cached += 1;
+        }
+    }
+    if cached > 0 {
+        cache.save()?;
+    }
+
+    println!(
+        "{} {} of {} activities",
+        "✅ Updated".bright_green().bold(),
+        updated.len().to_string().bright_green().bold(),
+        matching.len()
+    );
+    match failure {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{activity, of_type, on_gear};
+
+    #[test]
+    fn test_reconcile() {
+        let gear = vec![AthleteGear {
+            kind: GearKind::Bike,
+            primary: true,
+            gear: Gear {
+                id: "b1".to_string(),
+                name: Some("Canyon".to_string()),
+                brand_name: None,
+                model_name: None,
+                retired: false,
+                distance: 150_000.0,
+            },
+        }];
+        let activities = vec![
+            on_gear(activity(1, "2024-02-01", 50.0), "b1"),
+            on_gear(activity(2, "2024-02-01", 60.0), "b1"),
+            activity(3, "2024-02-01", 40.0),
+            of_type(activity(4, "2024-02-01", 10.0), "Run"),
+        ];
+
+        let (lines, unassigned, unassigned_km) =
+            reconcile(&gear, &activities, &["Ride".to_string()]);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].name, "Canyon");
+        assert_eq!(lines[0].activities, 2);
+        assert_eq!(lines[0].computed_km, 110.0);
+        assert_eq!(lines[0].difference_km(), 40.0);
+        assert_eq!(unassigned, 1);
+        assert_eq!(unassigned_km, 40.0);
+    }
+
+    #[test]
+    fn test_cached_gear_format() {
+        let gear: AthleteGear = serde_json::from_value(serde_json::json!({
+            "kind": "shoes",
+            "primary": false,
+            "id": "g1",
+            "name": null,
+            "brand_name": "Hoka",
+            "model_name": "Clifton",
+            "retired": true,
+            "distance": 500000.0
+        }))
+        .unwrap();
+        assert_eq!(gear.kind, GearKind::Shoes);
+        assert_eq!(gear.label(), "g1");
+        assert!(gear.gear.retired);
+    }
+
+    #[test]
+    fn test_assign_filter() {
+        let indoor = Activity {
+            name: "Zwift - Watopia".to_string(),
+            trainer: true,
+            ..on_gear(
+                of_type(activity(1, "2024-02-01", 30.0), "VirtualRide"),
+                "b1",
+            )
+        };
+        let outdoor = activity(2, "2024-02-01", 50.0);
+        let already = on_gear(
+            of_type(activity(3, "2024-02-01", 20.0), "VirtualRide"),
+            "b2",
+        );
+
+        let filter = AssignFilter {
+            allowed_types: vec!["Ride".to_string(), "VirtualRide".to_string()],
+            name: Some(Regex::new("(?i)^zwift").unwrap()),
+            trainer: None,
+        };
+        assert!(filter.matches(&indoor, "b2"));
+        assert!(!filter.matches(&outdoor, "b2"));
+        assert!(!filter.matches(&already, "b2"));
+
+        let outdoor_only = AssignFilter {
+            name: None,
+            trainer: Some(false),
+            ..filter
+        };
+        assert!(!outdoor_only.matches(&indoor, "b2"));
+        assert!(outdoor_only.matches(&outdoor, "b2"));
+    }
+}
@@ -0,0 +1,365 @@
+use crate::credentials;
+use crate::store;
+use anyhow::{Context, Result};
+use chrono::{NaiveDate, Utc};
+use colored::*;
+use serde::{Deserialize, Serialize};
+
+/// Width of the progress bar in characters, excluding the brackets
+const BAR_WIDTH: usize = 30;
+
+/// A distance target for a set of activity types over a date range
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Goal {
+    pub name: String,
+    pub distance_km: f64,
+    pub activity_types: String,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl Goal {
+    /// Number of days in the goal period, both ends included
+    pub fn total_days(&self) -> i64 {
+        (self.end - self.start).num_days() + 1
+    }
+}
+
+/// How far along a goal is on a given day
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct GoalProgress {
+    pub done_km: f64,
+    pub remaining_km: f64,
+    pub elapsed_days: i64,
+    pub remaining_days: i64,
+    /// Daily distance needed from now on to hit the target
+    pub required_daily_km: Option<f64>,
+    /// Average daily distance so far
+    pub current_daily_km: Option<f64>,
+    /// Total at the end of the period if the current pace is kept up
+    pub projected_km: f64,
+}
+
+impl GoalProgress {
+    pub fn compute(goal: &Goal, done_km: f64, today: NaiveDate) -> Self {
+        let total_days = goal.total_days();
+        let elapsed_days = ((today - goal.start).num_days() + 1).clamp(0, total_days);
+        let remaining_days = total_days - elapsed_days;
+        let remaining_km = (goal.distance_km - done_km).max(0.0);
+
+        let required_daily_km = (remaining_days > 0).then(|| remaining_km / remaining_days as f64);
+        let current_daily_km = (elapsed_days > 0).then(|| done_km / elapsed_days as f64);
+        let projected_km = done_km + current_daily_km.unwrap_or(0.0) * remaining_days as f64;
+
+        Self {
+            done_km,
+            remaining_km,
+            elapsed_days,
+            remaining_days,
+            required_daily_km,
+            current_daily_km,
+            projected_km,
+        }
+    }
+}
+
+/// Render `fraction` (0.0 to 1.0) as an ASCII progress bar, e.g. `[#####-----]`
+pub fn progress_bar(fraction: f64, width: usize) -> String {
+    let filled = ((fraction.clamp(0.0, 1.0) * width as f64).round() as usize).min(width);
+    format!("[{}{}]", "#".repeat(filled), "-".repeat(width - filled))
+}
+
+/// Work out the goal period from either a calendar year or an explicit range
+pub fn goal_period(
+    year: Option<i32>,
+    from: Option<String>,
+    to: Option<String>,
+) -> Result<(NaiveDate, NaiveDate)> {
+    let (start, end) = match (year, from, to) {
+        (Some(year), None, None) => (
+            NaiveDate::from_ymd_opt(year, 1, 1).context("Invalid year")?,
+            NaiveDate::from_ymd_opt(year, 12, 31).context("Invalid year")?,
+        ),
+        (None, Some(from), Some(to)) => (
+            crate::parse_date(&from).context("Failed to parse the provided start date")?,
+            crate::parse_date(&to).context("Failed to parse the provided end date")?,
+        ),
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Specify the goal period with either --year or both --from and --to"
+            ))
+        }
+    };
+
+    if end < start {
+        return Err(anyhow::anyhow!(
+            "End date must not be before the start date"
+        ));
+    }
+    Ok((start, end))
+}
+
+pub fn load_goals() -> Result<Vec<Goal>> {
+    store::load("goals")
+}
+
+pub fn handle_set(
+    name: String,
+    distance: f64,
+    activity_types: String,
+    year: Option<i32>,
+    from: Option<String>,
+    to: Option<String>,
+) -> Result<()> {
+    if distance <= 0.0 {
+        return Err(anyhow::anyhow!("Goal distance must be positive"));
+    }
+    // Validate the types now rather than when the status is first requested
+    crate::parse_activity_types(&activity_types)?;
+    let (start, end) = goal_period(year, from, to)?;
+
+    let mut goals = load_goals()?;
+    goals.retain(|goal| goal.name != name);
+    goals.push(Goal {
+        name: name.clone(),
+        distance_km: distance,
+        activity_types,
+        start,
+        end,
+    });
+    store::save("goals", &goals)?;
+
+    println!(
+        "{} {} {} km between {} and {}",
+        "🎯 Goal saved:".bright_green().bold(),
+        name.bright_white().bold(),
+        format!("{distance:.0}").bright_white().bold(),
+        start.to_string().bright_white(),
+        end.to_string().bright_white()
+    );
+    Ok(())
+}
+
+pub fn handle_list() -> Result<()> {
+    let goals = load_goals()?;
+    if goals.is_empty() {
+        println!(
+            "{}",
+            "No goals set. Use 'chain-life goal set' to add one.".yellow()
+        );
+        return Ok(());
+    }
+
+    println!("{}", "🎯 Goals:".bright_cyan().bold());
+    for goal in &goals {
+        println!(
+            "  {} {}: {} km of {} ({} to {})",
+            "•".bright_blue(),
+            goal.name.bright_white().bold(),
+            format!("{:.0}", goal.distance_km).bright_white(),
+            goal.activity_types.bright_blue(),
+            goal.start,
+            goal.end
+        );
+    }
+    Ok(())
+}
+
+pub fn handle_remove(name: String) -> Result<()> {
+    let mut goals = load_goals()?;
+    let before = goals.len();
+    goals.retain(|goal| goal.name != name);
+    if goals.len() == before {
+        return Err(anyhow::anyhow!("No goal named '{name}'"));
+    }
+    store::save("goals", &goals)?;
+
+    println!(
+        "{} {}",
+        "🗑️ Goal removed:".bright_green().bold(),
+        name.bright_white().bold()
+    );
+    Ok(())
+}
+
+pub async fn handle_status(
+    name: Option<String>,
+    token: Option<String>,
+    concurrency: usize,
+    verbose: bool,
+) -> Result<()> {
+    let goals: Vec<Goal> = load_goals()?
+        .into_iter()
+        .filter(|goal| name.as_ref().is_none_or(|name| &goal.name == name))
+        .collect();
+    if goals.is_empty() {
+        return Err(match name {
+            Some(name) => anyhow::anyhow!("No goal named '{name}'"),
+            None => anyhow::anyhow!("No goals set. Use 'chain-life goal set' to add one."),
+        });
+    }
+    let token = credentials::access_token(token, &[credentials::READ_ALL_ACTIVITIES]).await?;
+
+    let today = Utc::now().date_naive();
+    for (i, goal) in goals.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+
+        let done_km = if today < goal.start {
+            0.0
+        } else {
+            let allowed_types = crate::parse_activity_types(&goal.activity_types)?;
+            crate::fetch_strava_data_since(
+                goal.start,
+                Some(goal.end.min(today)),
+                token.clone(),
+                allowed_types,
+                concurrency,
+                verbose,
+            )
+            .await?
+        };
+
+        print_status(goal, &GoalProgress::compute(goal, done_km, today));
+    }
+    Ok(())
+}
+
+fn print_status(goal: &Goal, progress: &GoalProgress) {
+    let fraction = progress.done_km / goal.distance_km;
+    let bar = progress_bar(fraction, BAR_WIDTH);
+    let bar = if fraction >= 1.0 {
+        bar.bright_green()
+    } else {
+        bar.bright_yellow()
+    };
+
+    println!(
+        "{} {} ({}, {} to {})",
+        "🎯".bright_green(),
+        goal.name.bright_white().bold(),
+        goal.activity_types.bright_blue(),
+        goal.start,
+        goal.end
+    );
+    println!(
+        "  {} {}",
+        bar,
+        format!("{:.1}%", fraction * 100.0).bright_white().bold()
+    );
+    println!(
+        "  {} {} / {} km",
+        "Done:".cyan(),
+        format!("{:.1}", progress.done_km).bright_green().bold(),
+        format!("{:.0}", goal.distance_km).bright_white()
+    );
+    println!(
+        "  {} {} km ({} days left)",
+        "Remaining:".cyan(),
+        format!("{:.1}", progress.remaining_km)
+            .bright_white()
+            .bold(),
+        progress.remaining_days.to_string().bright_white()
+    );
+
+    if let Some(required) = progress.required_daily_km {
+        println!(
+            "  {} {} km/day, {} km/week",
+            "Required pace:".cyan(),
+            format!("{required:.1}").bright_white().bold(),
+            format!("{:.1}", required * 7.0).bright_white().bold()
+        );
+    }
+    if let Some(current) = progress.current_daily_km {
+        let on_track = progress.projected_km >= goal.distance_km;
+        let daily = format!("{current:.1}");
+        println!(
+            "  {} {} km/day, {} km/week",
+            "Current pace:".cyan(),
+            if on_track {
+                daily.bright_green().bold()
+            } else {
+                daily.bright_red().bold()
+            },
+            format!("{:.1}", current * 7.0).bright_white()
+        );
+        let projected = format!("{:.0}", progress.projected_km);
+        println!(
+            "  {} {} km {}",
+            "Projected total:".cyan(),
+            if on_track {
+                projected.bright_green().bold()
+            } else {
+                projected.bright_red().bold()
+            },
+            if on_track {
+                "✅ on track".bright_green()
+            } else {
+                "⚠️ behind pace".bright_red()
+            }
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn goal() -> Goal {
+        Goal {
+            name: "2025".to_string(),
+            distance_km: 7300.0,
+            activity_types: "cycling".to_string(),
+            start: NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(),
+            end: NaiveDate::from_ymd_opt(2025, 12, 31).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_progress_mid_period() {
+        let today = NaiveDate::from_ymd_opt(2025, 1, 10).unwrap();
+        let progress = GoalProgress::compute(&goal(), 100.0, today);
+        assert_eq!(progress.elapsed_days, 10);
+        assert_eq!(progress.remaining_days, 355);
+        assert_eq!(progress.remaining_km, 7200.0);
+        assert_eq!(progress.current_daily_km, Some(10.0));
+        assert!((progress.required_daily_km.unwrap() - 7200.0 / 355.0).abs() < 1e-9);
+        assert_eq!(progress.projected_km, 3650.0);
+    }
+
+    #[test]
+    fn test_progress_before_start() {
+        let today = NaiveDate::from_ymd_opt(2024, 12, 1).unwrap();
+        let progress = GoalProgress::compute(&goal(), 0.0, today);
+        assert_eq!(progress.elapsed_days, 0);
+        assert_eq!(progress.current_daily_km, None);
+        assert_eq!(progress.required_daily_km, Some(20.0));
+    }
+
+    #[test]
+    fn test_progress_after_end() {
+        let today = NaiveDate::from_ymd_opt(2026, 2, 1).unwrap();
+        let progress = GoalProgress::compute(&goal(), 8000.0, today);
+        assert_eq!(progress.remaining_days, 0);
+        assert_eq!(progress.remaining_km, 0.0);
+        assert_eq!(progress.required_daily_km, None);
+        assert_eq!(progress.projected_km, 8000.0);
+    }
+
+    #[test]
+    fn test_progress_bar() {
+        assert_eq!(progress_bar(0.5, 10), "[#####-----]");
+        assert_eq!(progress_bar(1.5, 4), "[####]");
+        assert_eq!(progress_bar(0.0, 4), "[----]");
+    }
+
+    #[test]
+    fn test_goal_period() {
+        let (start, end) = goal_period(Some(2025), None, None).unwrap();
+        assert_eq!(start, NaiveDate::from_ymd_opt(2025, 1, 1).unwrap());
+        assert_eq!(end, NaiveDate::from_ymd_opt(2025, 12, 31).unwrap());
+        assert!(goal_period(None, Some("2025-06-01".into()), None).is_err());
+        assert!(goal_period(None, Some("2025-06-01".into()), Some("2025-01-01".into())).is_err());
+    }
+}
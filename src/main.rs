@@ -1,9 +1,43 @@
+mod activity;
+mod alert;
+mod cache;
+mod chain;
+mod chart;
+mod component;
+mod config;
+mod cost;
+mod credentials;
+mod dashboard;
+mod export;
+mod fitness;
+mod gear;
+mod goal;
+mod metrics;
+mod report;
+mod server;
+mod service;
+mod stats;
+mod store;
+mod strava;
+#[cfg(test)]
+mod test_support;
+mod vault;
+mod webhook;
+
 use anyhow::{Context, Result};
-use chrono::NaiveDate;
+use chain::Treatment;
+use chart::{ChartKind, Period};
+use chrono::{NaiveDate, Utc};
 use clap::{Parser, Subcommand};
 use colored::*;
+use export::ExportFormat;
+use report::ReportFormat;
+use futures::TryStreamExt;
 use serde::{Deserialize, Serialize};
 use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use strava::{Activity, ActivityQuery};
 use url::Url;
 use uuid::Uuid;
 
@@ -66,33 +100,552 @@ struct Args {
 #[derive(Subcommand)]
 enum Commands {
     /// Authenticate with Strava using OAuth
+    ///
+    /// The Client Secret is read from STRAVA_CLIENT_SECRET, or prompted for so
+    /// that it doesn't end up in your shell history.
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
     Auth {
+        #[command(subcommand)]
+        command: Option<AuthCommands>,
+
         /// Your Strava application's Client ID
-        #[arg(short = 'i', long)]
-        client_id: String,
+        #[arg(short = 'i', long, env = "STRAVA_CLIENT_ID", required = true)]
+        client_id: Option<String>,
+
+        /// Comma-separated OAuth scopes to request (e.g. read,activity:read_all)
+        #[arg(long, default_value = credentials::DEFAULT_SCOPE)]
+        scope: String,
+
+        /// Redirect URI registered for your application; the pasted redirect must match it
+        #[arg(long, default_value = REDIRECT_URI)]
+        redirect_uri: String,
 
-        /// Your Strava application's Client Secret
-        #[arg(short = 's', long)]
-        client_secret: String,
+        /// Skip the browser and start from an existing refresh token, read from
+        /// STRAVA_REFRESH_TOKEN or prompted for
+        #[arg(long, conflicts_with_all = ["scope", "redirect_uri"])]
+        refresh_token: bool,
 
         /// Verbose output
         #[arg(short, long)]
         verbose: bool,
     },
+    /// Revoke chain-life's access to your Strava account and remove the saved
+    /// credentials and cached activities and gear
+    Logout,
+    /// Show which athlete and scopes the saved credentials are for, and when the token expires
+    Whoami,
     /// Fetch kilometers data from Strava
     Fetch {
         /// Start date in YYYY-MM-DD format
         #[arg(short, long)]
         date: String,
         
-        /// Strava access token
+        /// Strava access token (default: the credentials saved by 'auth')
         #[arg(short, long)]
-        token: String,
+        token: Option<String>,
         
         /// Activity types to include (comma-separated). Use 'cycling' for all cycling types, 'running' for all running types, or specify individual types
         #[arg(short = 'a', long, default_value = "cycling")]
         activity_types: String,
         
+        /// Maximum number of activity pages to request concurrently
+        #[arg(long, default_value_t = 4)]
+        concurrency: usize,
+
+        /// Render distance over time as a chart
+        #[arg(short, long, value_enum)]
+        chart: Option<ChartKind>,
+
+        /// Bucket size for bar charts and sparklines
+        #[arg(short, long, value_enum, default_value = "week")]
+        period: Period,
+
+        /// Verbose output
+        #[arg(short, long)]
+        verbose: bool,
+    },
+    /// Compute training stress and rolling fitness, fatigue and form
+    Fitness {
+        /// Start of the reported range in YYYY-MM-DD format
+        #[arg(long)]
+        from: String,
+
+        /// End of the reported range in YYYY-MM-DD format (default: today)
+        #[arg(long)]
+        to: Option<String>,
+
+        /// Strava access token (default: the credentials saved by 'auth')
+        #[arg(short, long)]
+        token: Option<String>,
+
+        /// Activity types to include (comma-separated), as for 'fetch'
+        #[arg(short = 'a', long, default_value = "cycling")]
+        activity_types: String,
+
+        /// Maximum number of activity pages to request concurrently
+        #[arg(long, default_value_t = 4)]
+        concurrency: usize,
+
+        /// Functional threshold power in watts (overrides the config file)
+        #[arg(long)]
+        ftp: Option<f64>,
+
+        /// Threshold heart rate in bpm (overrides the config file)
+        #[arg(long)]
+        threshold_hr: Option<f64>,
+
+        /// Export the daily training load to this file
+        #[arg(short, long)]
+        export: Option<PathBuf>,
+
+        /// Export file format
+        #[arg(short, long, value_enum, default_value = "csv")]
+        format: ExportFormat,
+
+        /// Verbose output
+        #[arg(short, long)]
+        verbose: bool,
+    },
+    /// Inspect individual activities
+    Activity {
+        #[command(subcommand)]
+        command: ActivityCommands,
+    },
+    /// Set distance goals and track progress towards them
+    Goal {
+        #[command(subcommand)]
+        command: GoalCommands,
+    },
+    /// Track chain stretch, rotation and waxing
+    Chain {
+        #[command(subcommand)]
+        command: ChainCommands,
+    },
+    /// Log maintenance done on each bike and show it alongside the distance ridden
+    Service {
+        #[command(subcommand)]
+        command: ServiceCommands,
+    },
+    /// Show cost per km of each bike and kind of component
+    Cost {
+        /// Report spending and distance in this calendar year instead of running totals
+        #[arg(short, long)]
+        year: Option<i32>,
+    },
+    /// List the athlete's bikes and shoes and compare their distance with activities
+    Gear {
+        #[command(subcommand)]
+        command: GearCommands,
+    },
+    /// Generate a season summary report
+    Report {
+        /// Report format
+        #[arg(short, long, value_enum, default_value = "html")]
+        format: ReportFormat,
+
+        /// Write the report to this file instead of standard output
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+
+        /// Calendar year to report on (default: the current year)
+        #[arg(short, long, conflicts_with_all = ["from", "to"])]
+        year: Option<i32>,
+
+        /// Start of the reported period in YYYY-MM-DD format
+        #[arg(long, requires = "to")]
+        from: Option<String>,
+
+        /// End of the reported period in YYYY-MM-DD format
+        #[arg(long, requires = "from")]
+        to: Option<String>,
+
+        /// Report title
+        #[arg(long)]
+        title: Option<String>,
+
+        /// Strava access token (default: the credentials saved by 'auth')
+        #[arg(short, long)]
+        token: Option<String>,
+
+        /// Activity types to include (comma-separated), as for 'fetch'
+        #[arg(short = 'a', long, default_value = "cycling")]
+        activity_types: String,
+
+        /// Maximum number of activity pages to request concurrently
+        #[arg(long, default_value_t = 4)]
+        concurrency: usize,
+
+        /// Verbose output
+        #[arg(short, long)]
+        verbose: bool,
+    },
+    /// Full-screen dashboard with totals, recent activities, component wear and goals
+    Dashboard {
+        /// Strava access token (default: the credentials saved by 'auth')
+        #[arg(short, long)]
+        token: Option<String>,
+
+        /// Maximum number of activity pages to request concurrently
+        #[arg(long, default_value_t = 4)]
+        concurrency: usize,
+    },
+    /// Download new activities into the local cache
+    Sync {
+        /// Strava access token (default: the credentials saved by 'auth')
+        #[arg(short, long)]
+        token: Option<String>,
+
+        /// Maximum number of activity pages to request concurrently
+        #[arg(long, default_value_t = 4)]
+        concurrency: usize,
+
+        /// Re-check this many recent days for activities edited or deleted on Strava
+        /// (default: reconcile_days from the config file, or 30; 0 disables)
+        #[arg(long)]
+        reconcile_days: Option<u32>,
+
+        /// Verbose output
+        #[arg(short, long)]
+        verbose: bool,
+    },
+    /// Keep the local cache up to date from Strava push events
+    Webhook {
+        #[command(subcommand)]
+        command: WebhookCommands,
+    },
+    /// Show Strava's recent, year-to-date and all-time ride, run and swim totals
+    Stats {
+        /// Strava access token (default: the credentials saved by 'auth')
+        #[arg(short, long)]
+        token: Option<String>,
+
+        /// Cross-check the totals against the local activity cache
+        #[arg(long)]
+        check: bool,
+    },
+    /// Write activity totals and component wear in Prometheus text format
+    ExportMetrics {
+        /// Write to this file (e.g. in node_exporter's textfile directory) instead of standard output
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+    /// Serve totals, activities and components from the local cache as JSON over HTTP
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        addr: String,
+
+        /// Sync the cache with the saved credentials every this many minutes
+        #[arg(long)]
+        sync_interval: Option<u64>,
+
+        /// Maximum number of activity pages to request concurrently when syncing
+        #[arg(long, default_value_t = 4)]
+        concurrency: usize,
+    },
+}
+
+#[derive(Subcommand)]
+enum AuthCommands {
+    /// Write the saved credentials to a passphrase-encrypted bundle, to import on another machine
+    Export {
+        /// File to write the bundle to (default: standard output)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+
+        /// Remove the credentials from this machine once exported
+        #[arg(long)]
+        remove: bool,
+    },
+    /// Save the credentials from a bundle written by 'auth export'
+    Import {
+        /// Bundle file, or - for standard input
+        file: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum ChainCommands {
+    /// Log a chain checker reading
+    Measure {
+        /// Name of the chain in the config file's components
+        name: String,
+
+        /// Elongation in percent, e.g. 0.5
+        elongation: f64,
+
+        /// Day of the measurement in YYYY-MM-DD format (default: today)
+        #[arg(short, long)]
+        date: Option<String>,
+    },
+    /// Show logged measurements
+    Log {
+        /// Only show measurements of this chain
+        name: Option<String>,
+    },
+    /// Record putting a chain on a bike, replacing the chain that was on it
+    Swap {
+        /// Name of the chain
+        chain: String,
+
+        /// Strava gear ID of the bike
+        #[arg(short, long)]
+        bike: String,
+
+        /// Day of the swap in YYYY-MM-DD format (default: today)
+        #[arg(short, long)]
+        date: Option<String>,
+    },
+    /// Record waxing or lubing a chain
+    Treat {
+        /// Name of the chain
+        chain: String,
+
+        /// What the chain was treated with
+        #[arg(short, long, value_enum, default_value = "wax")]
+        treatment: Treatment,
+
+        /// Day of the treatment in YYYY-MM-DD format (default: today)
+        #[arg(short, long)]
+        date: Option<String>,
+    },
+    /// Show total distance and distance since the last treatment of each chain in rotation
+    Rotation,
+    /// Fit a wear rate to the measurements and predict when the chain reaches the threshold
+    Predict {
+        /// Name of the chain in the config file's components
+        name: String,
+
+        /// Elongation in percent at which the chain should be replaced
+        #[arg(long, default_value_t = 0.75)]
+        threshold: f64,
+    },
+}
+
+#[derive(Subcommand)]
+enum ServiceCommands {
+    /// Record a maintenance event
+    Add {
+        /// What was done, e.g. "chain replaced", "brake bleed" or "tyre swap"
+        kind: String,
+
+        /// Strava gear ID of the bike
+        #[arg(short, long)]
+        bike: String,
+
+        /// Day of the service in YYYY-MM-DD format (default: today)
+        #[arg(short, long)]
+        date: Option<String>,
+
+        /// What the service cost
+        #[arg(short, long)]
+        cost: Option<f64>,
+
+        /// Free-form notes
+        #[arg(short, long)]
+        notes: Option<String>,
+    },
+    /// Remove a maintenance event
+    Remove {
+        /// Number of the event, as shown by 'service history'
+        id: u32,
+    },
+    /// Show maintenance events per bike with the distance ridden between them
+    History {
+        /// Only show this bike (Strava gear ID)
+        #[arg(short, long)]
+        bike: Option<String>,
+
+        /// Strava access token (default: the credentials saved by 'auth')
+        #[arg(short, long)]
+        token: Option<String>,
+
+        /// Activity types to count (comma-separated), as for 'fetch'
+        #[arg(short = 'a', long, default_value = "cycling")]
+        activity_types: String,
+
+        /// Maximum number of activity pages to request concurrently
+        #[arg(long, default_value_t = 4)]
+        concurrency: usize,
+
+        /// Verbose output
+        #[arg(short, long)]
+        verbose: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum GearCommands {
+    /// Fetch and cache the athlete's gear with brand, model and total distance
+    List {
+        /// Strava access token (default: the credentials saved by 'auth')
+        #[arg(short, long)]
+        token: Option<String>,
+
+        /// Verbose output
+        #[arg(short, long)]
+        verbose: bool,
+    },
+    /// Move matching activities to a piece of gear (needs the activity:write scope)
+    Assign {
+        /// Strava gear ID to assign the activities to
+        gear_id: String,
+
+        /// First day to look at in YYYY-MM-DD format
+        #[arg(long)]
+        from: String,
+
+        /// Last day to look at in YYYY-MM-DD format (default: today)
+        #[arg(long)]
+        to: Option<String>,
+
+        /// Activity types to reassign (comma-separated), as for 'fetch'
+        #[arg(short = 'a', long, default_value = "cycling")]
+        activity_types: String,
+
+        /// Only activities whose name matches this regular expression
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Only activities recorded on an indoor trainer
+        #[arg(long, conflicts_with = "outdoor")]
+        trainer: bool,
+
+        /// Only activities not recorded on an indoor trainer
+        #[arg(long)]
+        outdoor: bool,
+
+        /// Show the activities that would be reassigned without changing them
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Strava access token (default: the credentials saved by 'auth')
+        #[arg(short, long)]
+        token: Option<String>,
+
+        /// Maximum number of activity pages to request concurrently
+        #[arg(long, default_value_t = 4)]
+        concurrency: usize,
+
+        /// Verbose output
+        #[arg(short, long)]
+        verbose: bool,
+    },
+    /// Compare Strava's gear distance with the cached activities assigned to each piece of gear
+    Reconcile {
+        /// Activity types to check for missing gear (comma-separated), as for 'fetch'
+        #[arg(short = 'a', long, default_value = "all")]
+        activity_types: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum WebhookCommands {
+    /// Answer the subscription validation challenge and apply received events to the cache
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        addr: String,
+
+        /// Token that the subscription was created with
+        #[arg(long)]
+        verify_token: String,
+    },
+    /// Create the push subscription; 'webhook serve' must already be reachable at the callback URL
+    Subscribe {
+        /// Public URL that Strava will POST events to, ending in /webhook
+        #[arg(long)]
+        callback_url: String,
+
+        /// Token Strava echoes back when validating the callback URL
+        #[arg(long)]
+        verify_token: String,
+    },
+    /// List the push subscriptions of the application
+    List,
+    /// Delete a push subscription
+    Unsubscribe {
+        /// ID of the subscription
+        id: i64,
+    },
+}
+
+#[derive(Subcommand)]
+enum GoalCommands {
+    /// Create or replace a distance goal
+    Set {
+        /// Name of the goal
+        name: String,
+
+        /// Target distance in kilometers
+        #[arg(short, long)]
+        distance: f64,
+
+        /// Activity types counting towards the goal (comma-separated), as for 'fetch'
+        #[arg(short = 'a', long, default_value = "cycling")]
+        activity_types: String,
+
+        /// Calendar year of the goal
+        #[arg(short, long, conflicts_with_all = ["from", "to"])]
+        year: Option<i32>,
+
+        /// Start of the goal period in YYYY-MM-DD format
+        #[arg(long, requires = "to")]
+        from: Option<String>,
+
+        /// End of the goal period in YYYY-MM-DD format
+        #[arg(long, requires = "from")]
+        to: Option<String>,
+    },
+    /// List all goals
+    List,
+    /// Remove a goal
+    Remove {
+        /// Name of the goal
+        name: String,
+    },
+    /// Show progress, pace and projection for goals
+    Status {
+        /// Only show the goal with this name
+        name: Option<String>,
+
+        /// Strava access token (default: the credentials saved by 'auth')
+        #[arg(short, long)]
+        token: Option<String>,
+
+        /// Maximum number of activity pages to request concurrently
+        #[arg(long, default_value_t = 4)]
+        concurrency: usize,
+
+        /// Verbose output
+        #[arg(short, long)]
+        verbose: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ActivityCommands {
+    /// Show detailed data for a single activity
+    Show {
+        /// Strava activity ID
+        id: i64,
+
+        /// Strava access token (default: the credentials saved by 'auth')
+        #[arg(short, long)]
+        token: Option<String>,
+
+        /// Also fetch the activity's data streams (power, heart rate, GPS, ...)
+        #[arg(short, long)]
+        streams: bool,
+
+        /// Export the streams to this file (implies --streams)
+        #[arg(short, long)]
+        export: Option<PathBuf>,
+
+        /// Export file format
+        #[arg(short, long, value_enum, default_value = "csv")]
+        format: ExportFormat,
+
         /// Verbose output
         #[arg(short, long)]
         verbose: bool,
@@ -120,48 +673,253 @@ struct AthleteInfo {
     country: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Activity {
-    id: i64,
-    name: String,
-    distance: f64,
-    moving_time: i32,
-    elapsed_time: i32,
-    total_elevation_gain: f64,
-    #[serde(rename = "type")]
-    activity_type: String,
-    start_date: String,
-}
-
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
 
     match args.command {
         Commands::Auth {
+            command: Some(AuthCommands::Export { out, remove }),
+            ..
+        } => credentials::handle_export(out, remove),
+        Commands::Auth {
+            command: Some(AuthCommands::Import { file }),
+            ..
+        } => credentials::handle_import(file),
+        Commands::Auth {
+            command: None,
             client_id,
-            client_secret,
+            scope,
+            redirect_uri,
+            refresh_token,
             verbose,
-        } => handle_auth(client_id, client_secret, verbose).await,
+        } => {
+            // Required unless a subcommand is given
+            let client_id = client_id.context("--client-id is required")?;
+            if refresh_token {
+                handle_auth_refresh_token(client_id).await
+            } else {
+                handle_auth(client_id, scope, redirect_uri, verbose).await
+            }
+        }
+        Commands::Logout => credentials::handle_logout().await,
+        Commands::Whoami => credentials::handle_whoami(),
         Commands::Fetch {
             date,
             token,
             activity_types,
+            concurrency,
+            chart,
+            period,
+            verbose,
+        } => {
+            handle_fetch(date, token, activity_types, concurrency, chart, period, verbose).await
+        }
+        Commands::Fitness {
+            from,
+            to,
+            token,
+            activity_types,
+            concurrency,
+            ftp,
+            threshold_hr,
+            export,
+            format,
             verbose,
-        } => handle_fetch(date, token, activity_types, verbose).await,
+        } => {
+            fitness::handle_fitness(
+                from,
+                to,
+                token,
+                activity_types,
+                concurrency,
+                ftp,
+                threshold_hr,
+                export,
+                format,
+                verbose,
+            )
+            .await
+        }
+        Commands::Activity { command } => match command {
+            ActivityCommands::Show {
+                id,
+                token,
+                streams,
+                export,
+                format,
+                verbose,
+            } => activity::handle_show(id, token, streams, export, format, verbose).await,
+        },
+        Commands::Goal { command } => match command {
+            GoalCommands::Set {
+                name,
+                distance,
+                activity_types,
+                year,
+                from,
+                to,
+            } => goal::handle_set(name, distance, activity_types, year, from, to),
+            GoalCommands::List => goal::handle_list(),
+            GoalCommands::Remove { name } => goal::handle_remove(name),
+            GoalCommands::Status {
+                name,
+                token,
+                concurrency,
+                verbose,
+            } => goal::handle_status(name, token, concurrency, verbose).await,
+        },
+        Commands::Chain { command } => match command {
+            ChainCommands::Measure {
+                name,
+                elongation,
+                date,
+            } => chain::handle_measure(name, elongation, date),
+            ChainCommands::Log { name } => chain::handle_log(name),
+            ChainCommands::Swap { chain, bike, date } => chain::handle_swap(chain, bike, date),
+            ChainCommands::Treat {
+                chain,
+                treatment,
+                date,
+            } => chain::handle_treat(chain, treatment, date),
+            ChainCommands::Rotation => chain::handle_rotation(),
+            ChainCommands::Predict { name, threshold } => chain::handle_predict(name, threshold),
+        },
+        Commands::Service { command } => match command {
+            ServiceCommands::Add {
+                kind,
+                bike,
+                date,
+                cost,
+                notes,
+            } => service::handle_add(bike, kind, date, cost, notes),
+            ServiceCommands::Remove { id } => service::handle_remove(id),
+            ServiceCommands::History {
+                bike,
+                token,
+                activity_types,
+                concurrency,
+                verbose,
+            } => {
+                service::handle_history(bike, token, activity_types, concurrency, verbose).await
+            }
+        },
+        Commands::Cost { year } => cost::handle_cost(year),
+        Commands::Gear { command } => match command {
+            GearCommands::List { token, verbose } => gear::handle_list(token, verbose).await,
+            GearCommands::Reconcile { activity_types } => gear::handle_reconcile(activity_types),
+            GearCommands::Assign {
+                gear_id,
+                from,
+                to,
+                activity_types,
+                name,
+                trainer,
+                outdoor,
+                dry_run,
+                token,
+                concurrency,
+                verbose,
+            } => {
+                let trainer = match (trainer, outdoor) {
+                    (true, _) => Some(true),
+                    (_, true) => Some(false),
+                    _ => None,
+                };
+                gear::handle_assign(
+                    gear_id,
+                    from,
+                    to,
+                    activity_types,
+                    name,
+                    trainer,
+                    dry_run,
+                    token,
+                    concurrency,
+                    verbose,
+                )
+                .await
+            }
+        },
+        Commands::Report {
+            format,
+            out,
+            year,
+            from,
+            to,
+            title,
+            token,
+            activity_types,
+            concurrency,
+            verbose,
+        } => {
+            report::handle_report(
+                format,
+                out,
+                year,
+                from,
+                to,
+                title,
+                token,
+                activity_types,
+                concurrency,
+                verbose,
+            )
+            .await
+        }
+        Commands::Dashboard { token, concurrency } => {
+            dashboard::handle_dashboard(token, concurrency).await
+        }
+        Commands::Sync {
+            token,
+            concurrency,
+            reconcile_days,
+            verbose,
+        } => cache::handle_sync(token, concurrency, reconcile_days, verbose).await,
+        Commands::Webhook { command } => match command {
+            WebhookCommands::Serve { addr, verify_token } => {
+                webhook::handle_serve(addr, verify_token).await
+            }
+            WebhookCommands::Subscribe {
+                callback_url,
+                verify_token,
+            } => webhook::handle_subscribe(callback_url, verify_token).await,
+            WebhookCommands::List => webhook::handle_list().await,
+            WebhookCommands::Unsubscribe { id } => webhook::handle_unsubscribe(id).await,
+        },
+        Commands::Stats { token, check } => stats::handle_stats(token, check).await,
+        Commands::ExportMetrics { out } => metrics::handle_export_metrics(out),
+        Commands::Serve {
+            addr,
+            sync_interval,
+            concurrency,
+        } => server::handle_serve(addr, sync_interval, concurrency).await,
     }
 }
 
-async fn handle_auth(client_id: String, client_secret: String, verbose: bool) -> Result<()> {
+async fn handle_auth(
+    client_id: String,
+    scope: String,
+    redirect_uri: String,
+    verbose: bool,
+) -> Result<()> {
+    let requested = credentials::parse_scopes(&scope);
+    if requested.is_empty() {
+        return Err(anyhow::anyhow!("No scopes given. Use e.g. --scope {}", credentials::DEFAULT_SCOPE));
+    }
+    let redirect_uri = Url::parse(&redirect_uri).context("Invalid --redirect-uri")?;
+    let client_secret = vault::read_secret(CLIENT_SECRET_ENV, "Client Secret", false)?.trim().to_string();
+
     if verbose {
         println!("{}", "🔐 Starting Strava OAuth authentication...".bright_cyan().bold());
     }
 
     // Generate a unique state parameter for security
     let state = Uuid::new_v4().to_string();
+    let started = Instant::now();
 
     // Build the authorization URL
-    let auth_url = build_auth_url(&client_id, &state)?;
+    let auth_url = build_auth_url(&client_id, &state, &requested.join(","), &redirect_uri)?;
 
     println!("{}", "🔗 Please open this URL in your browser to authorize the application:".bright_cyan().bold());
     println!("{}", auth_url.blue().underline());
@@ -172,47 +930,94 @@ async fn handle_auth(client_id: String, client_secret: String, verbose: bool) ->
     print!("{}", "Enter the redirect URL: ".green().bold());
     io::stdout().flush()?;
 
-    let mut input = String::new();
-    io::stdin().read_line(&mut input)?;
+    let input = read_line_within(AUTH_SESSION_TIMEOUT.saturating_sub(started.elapsed()))
+        .await?
+        .with_context(|| {
+            format!(
+                "Authorization session timed out after {} minutes. Run 'chain-life auth' again",
+                AUTH_SESSION_TIMEOUT.as_secs() / 60
+            )
+        })?;
     let redirect_url = input.trim();
 
-    if verbose {
-        println!("{} {}", "Processing redirect URL:".dimmed(), redirect_url.dimmed());
-    }
-
     // Extract the authorization code from the redirect URL
-    let auth_code = extract_auth_code(redirect_url, &state)?;
+    let redirect = extract_auth_code(redirect_url, &state, &redirect_uri)?;
 
     if verbose {
-        println!("{} {}", "Extracted authorization code:".dimmed(), auth_code.dimmed());
+        println!("{} {}", "Extracted authorization code:".dimmed(), credentials::redact(&redirect.code).dimmed());
     }
 
     // Exchange the authorization code for tokens
-    let token_response = exchange_code_for_token(&client_id, &client_secret, &auth_code).await?;
+    let token_response = exchange_code_for_token(&client_id, &client_secret, &redirect.code).await?;
 
-    println!("{}", "✅ Authentication successful!".bright_green().bold());
-    println!(
-        "{} {} {}",
-        "🏃 Athlete:".bright_cyan().bold(),
-        token_response.athlete.firstname.unwrap_or_default().bright_white().bold(),
-        token_response.athlete.lastname.unwrap_or_default().bright_white().bold()
+    let athlete_name = format!(
+        "{} {}",
+        token_response.athlete.firstname.clone().unwrap_or_default(),
+        token_response.athlete.lastname.clone().unwrap_or_default()
     );
-    println!("{} {}", "🔑 Access Token:".bright_yellow().bold(), token_response.access_token.bright_white());
-    println!("{} {}", "🔄 Refresh Token:".bright_blue().bold(), token_response.refresh_token.bright_white());
+
+    println!("{}", "✅ Authentication successful!".bright_green().bold());
+    println!("{} {}", "🏃 Athlete:".bright_cyan().bold(), athlete_name.bright_white().bold());
+    if verbose {
+        println!("{} {}", "🔑 Access Token:".bright_yellow().bold(), credentials::redact(&token_response.access_token).bright_white());
+        println!("{} {}", "🔄 Refresh Token:".bright_blue().bold(), credentials::redact(&token_response.refresh_token).bright_white());
+    }
     println!("{} {}", "⏰ Token expires at:".bright_magenta().bold(), token_response.expires_at.to_string().bright_white());
+    match &redirect.scopes {
+        Some(granted) => {
+            println!("{} {}", "🔐 Granted scopes:".bright_cyan().bold(), granted.join(",").bright_white());
+            let missing: Vec<&str> = requested
+                .iter()
+                .filter(|scope| !credentials::has_scope(granted, scope))
+                .map(String::as_str)
+                .collect();
+            if !missing.is_empty() {
+                println!(
+                    "{} {}",
+                    "⚠️  Not granted, commands that need these will fail:".yellow(),
+                    missing.join(",").yellow().bold()
+                );
+            }
+        }
+        None => println!("{}", "⚠️  The redirect URL didn't say which scopes were granted".yellow()),
+    }
     println!();
-    println!("{}", "💡 Save your access token to use with the 'fetch' command:".bright_cyan().bold());
     println!(
-        "   {} {}",
-        "chain-life fetch --date 2024-01-01 --token".dimmed(),
-        token_response.access_token.bright_green()
+        "{} {}",
+        "💡 Tokens aren't shown; commands without a --token use the saved ones, e.g.".bright_cyan().bold(),
+        "chain-life sync".bright_green()
     );
     println!();
 
+    let credentials = credentials::Credentials {
+        client_id,
+        client_secret,
+        access_token: token_response.access_token,
+        refresh_token: token_response.refresh_token,
+        expires_at: token_response.expires_at,
+        athlete_id: token_response.athlete.id,
+        athlete_name: Some(athlete_name.trim().to_string()).filter(|name| !name.is_empty()),
+        scopes: redirect.scopes,
+    };
+    let location = credentials.save()?;
+    println!(
+        "{} {}",
+        "💾 Credentials saved to".bright_cyan().bold(),
+        location.to_string().bright_white()
+    );
+
     Ok(())
 }
 
-async fn handle_fetch(date: String, token: String, activity_types: String, verbose: bool) -> Result<()> {
+async fn handle_fetch(
+    date: String,
+    token: Option<String>,
+    activity_types: String,
+    concurrency: usize,
+    chart: Option<ChartKind>,
+    period: Period,
+    verbose: bool,
+) -> Result<()> {
     if verbose {
         println!("{}", "🚀 Starting Strava data fetch...".bright_cyan().bold());
     }
@@ -233,43 +1038,185 @@ async fn handle_fetch(date: String, token: String, activity_types: String, verbo
     }
     
     // Fetch activities from Strava
-    let total_km = fetch_strava_data_since(start_date, token, allowed_types, verbose).await?;
+    let token = credentials::access_token(token, &[credentials::READ_ALL_ACTIVITIES]).await?;
+    let activities =
+        fetch_activities_since(start_date, None, token, allowed_types, concurrency, verbose)
+            .await?;
     
+    if let Some(chart) = chart {
+        print_chart(&activities, chart, period, start_date);
+        println!();
+    }
+
     println!("{} {}: {} km", 
              "🚴 Total kilometers since".bright_green().bold(),
              date.bright_white().bold(),
-             format!("{:.2}", total_km).bright_green().bold());
+             format!("{:.2}", total_km(&activities)).bright_green().bold());
     
     Ok(())
 }
 
-fn build_auth_url(client_id: &str, state: &str) -> Result<String> {
+fn print_chart(activities: &[Activity], kind: ChartKind, period: Period, start_date: NaiveDate) {
+    let days = chart::daily_distances(activities);
+    let today = Utc::now().date_naive();
+    let width = chart::terminal_width();
+
+    match kind {
+        ChartKind::Bars => {
+            let buckets = chart::bucket_distances(&days, period, start_date, today);
+            println!("{}", "📊 Distance per period:".bright_cyan().bold());
+            for line in chart::bar_chart(&buckets, width) {
+                println!("{line}");
+            }
+        }
+        ChartKind::Sparkline => {
+            let buckets = chart::bucket_distances(&days, period, start_date, today);
+            let values: Vec<f64> = buckets.iter().map(|(_, km)| *km).collect();
+            let prefix = "📈 Distance per period: ";
+            let line = chart::sparkline(&values, width.saturating_sub(prefix.chars().count() + 2));
+            println!("{}{}", prefix.bright_cyan().bold(), line.bright_green());
+        }
+        ChartKind::Heatmap => {
+            println!("{}", "🗓️ Days with activities:".bright_cyan().bold());
+            for line in chart::heatmap(&days, start_date, today, width) {
+                println!("{line}");
+            }
+        }
+    }
+}
+
+/// Environment variable holding the Client Secret for `auth`
+const CLIENT_SECRET_ENV: &str = "STRAVA_CLIENT_SECRET";
+
+/// Environment variable holding the refresh token for `auth --refresh-token`
+const REFRESH_TOKEN_ENV: &str = "STRAVA_REFRESH_TOKEN";
+
+/// Set up the saved credentials from an existing refresh token, for machines
+/// without a browser
+async fn handle_auth_refresh_token(client_id: String) -> Result<()> {
+    let client_secret = vault::read_secret(CLIENT_SECRET_ENV, "Client Secret", false)?.trim().to_string();
+    let refresh_token = vault::read_secret(REFRESH_TOKEN_ENV, "refresh token", false)?.trim().to_string();
+
+    let mut credentials = credentials::Credentials {
+        client_id,
+        client_secret,
+        access_token: String::new(),
+        refresh_token,
+        expires_at: 0,
+        athlete_id: 0,
+        athlete_name: None,
+        // A refresh doesn't say which scopes were granted
+        scopes: None,
+    };
+    credentials.refresh().await?;
+    // Strava has now replaced the refresh token that was passed in, so keep the
+    // new one even if looking up the athlete fails
+    credentials.save()?;
+    let athlete = strava::get_athlete(&reqwest::Client::new(), &credentials.access_token)
+        .await
+        .context("Saved the refreshed credentials, but failed to look up the athlete")?;
+    let athlete_name = format!(
+        "{} {}",
+        athlete.firstname.unwrap_or_default(),
+        athlete.lastname.unwrap_or_default()
+    );
+    credentials.athlete_id = athlete.id;
+    credentials.athlete_name = Some(athlete_name.trim().to_string()).filter(|name| !name.is_empty());
+
+    println!("{}", "✅ Authentication successful!".bright_green().bold());
+    println!(
+        "{} {}",
+        "🏃 Athlete:".bright_cyan().bold(),
+        credentials.athlete_name.as_deref().unwrap_or("(no name)").bright_white().bold()
+    );
+    println!(
+        "{}",
+        "⚠️  Scopes can't be read from a refresh token, so commands won't check them".yellow()
+    );
+    let location = credentials.save()?;
+    println!(
+        "{} {}",
+        "💾 Credentials saved to".bright_cyan().bold(),
+        location.to_string().bright_white()
+    );
+    Ok(())
+}
+
+/// Redirect URI used unless `auth --redirect-uri` says otherwise
+const REDIRECT_URI: &str = "http://localhost/exchange_token";
+
+/// How long the user has to authorize and paste the redirect URL back
+const AUTH_SESSION_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Read a line from stdin, or `None` if none was entered within `timeout`.
+/// The read runs on its own thread: one left waiting after a timeout doesn't
+/// hold up the runtime's shutdown the way a blocking task would.
+async fn read_line_within(timeout: Duration) -> Result<Option<String>> {
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+    std::thread::spawn(move || {
+        let mut input = String::new();
+        let _ = sender.send(io::stdin().read_line(&mut input).map(|_| input));
+    });
+
+    match tokio::time::timeout(timeout, receiver).await {
+        Ok(read) => Ok(Some(read.context("Failed to read from stdin")??)),
+        Err(_) => Ok(None),
+    }
+}
+
+fn build_auth_url(client_id: &str, state: &str, scope: &str, redirect_uri: &Url) -> Result<String> {
     let mut url = Url::parse("https://www.strava.com/oauth/authorize")?;
 
     url.query_pairs_mut()
         .append_pair("client_id", client_id)
         .append_pair("response_type", "code")
-        .append_pair("redirect_uri", "http://localhost/exchange_token")
+        .append_pair("redirect_uri", redirect_uri.as_str())
         .append_pair("approval_prompt", "force")
-        .append_pair("scope", "read,activity:read_all")
+        .append_pair("scope", scope)
         .append_pair("state", state);
 
     Ok(url.to_string())
 }
 
-fn extract_auth_code(redirect_url: &str, expected_state: &str) -> Result<String> {
+/// What Strava passed back on the redirect after authorization
+#[derive(Debug, PartialEq)]
+struct AuthRedirect {
+    code: String,
+    /// Scopes the athlete actually granted, which may be fewer than requested
+    scopes: Option<Vec<String>>,
+}
+
+fn extract_auth_code(redirect_url: &str, expected_state: &str, redirect_uri: &Url) -> Result<AuthRedirect> {
     let url = Url::parse(redirect_url).context("Invalid redirect URL format")?;
 
+    // Only accept the redirect we asked Strava for
+    if url.scheme() != redirect_uri.scheme()
+        || url.host_str() != redirect_uri.host_str()
+        || url.port_or_known_default() != redirect_uri.port_or_known_default()
+        || url.path() != redirect_uri.path()
+    {
+        return Err(anyhow::anyhow!(
+            "Redirect URL doesn't match the redirect URI {}",
+            redirect_uri
+        ));
+    }
+
     let query_pairs: std::collections::HashMap<String, String> =
         url.query_pairs().into_owned().collect();
 
     // Verify state parameter for security
-    if let Some(state) = query_pairs.get("state") {
-        if state != expected_state {
+    match query_pairs.get("state") {
+        Some(state) if state == expected_state => {}
+        Some(_) => {
             return Err(anyhow::anyhow!(
                 "State parameter mismatch. Possible CSRF attack."
             ));
         }
+        None => {
+            return Err(anyhow::anyhow!(
+                "No state parameter in redirect URL. Possible CSRF attack."
+            ));
+        }
     }
 
     // Check for authorization errors
@@ -278,10 +1225,14 @@ fn extract_auth_code(redirect_url: &str, expected_state: &str) -> Result<String>
     }
 
     // Extract the authorization code
-    query_pairs
+    let code = query_pairs
         .get("code")
-        .ok_or_else(|| anyhow::anyhow!("No authorization code found in redirect URL"))
-        .map(|code| code.to_string())
+        .ok_or_else(|| anyhow::anyhow!("No authorization code found in redirect URL"))?;
+
+    Ok(AuthRedirect {
+        code: code.to_string(),
+        scopes: query_pairs.get("scope").map(|scope| credentials::parse_scopes(scope)),
+    })
 }
 
 async fn exchange_code_for_token(
@@ -318,111 +1269,103 @@ fn parse_date(date_str: &str) -> Result<NaiveDate> {
     NaiveDate::parse_from_str(date_str, "%Y-%m-%d").context("Date must be in YYYY-MM-DD format")
 }
 
-/// Fetch Strava data since the given date
+/// Fetch Strava data since the given date, optionally up to and including `end_date`
 async fn fetch_strava_data_since(
     start_date: NaiveDate,
+    end_date: Option<NaiveDate>,
     token: String,
     allowed_types: Vec<String>,
+    concurrency: usize,
     verbose: bool,
 ) -> Result<f64> {
+    let activities = fetch_activities_since(
+        start_date,
+        end_date,
+        token,
+        allowed_types,
+        concurrency,
+        verbose,
+    )
+    .await?;
+
+    // Convert from meters to kilometers
+    Ok(total_km(&activities))
+}
+
+/// Total distance of `activities` in kilometers
+fn total_km(activities: &[Activity]) -> f64 {
+    activities.iter().map(|activity| activity.distance).sum::<f64>() / 1000.0
+}
+
+/// Fetch the activities of the allowed types since the given date, optionally
+/// up to and including `end_date`
+async fn fetch_activities_since(
+    start_date: NaiveDate,
+    end_date: Option<NaiveDate>,
+    token: String,
+    allowed_types: Vec<String>,
+    concurrency: usize,
+    verbose: bool,
+) -> Result<Vec<Activity>> {
     let client = reqwest::Client::new();
 
     // Convert start_date to Unix timestamp
-    let start_timestamp = start_date
-        .and_hms_opt(0, 0, 0)
-        .unwrap()
-        .and_utc()
-        .timestamp();
+    let start_timestamp = strava::day_timestamp(start_date);
 
     if verbose {
         println!("{} {}", "📡 Fetching activities since timestamp:".cyan(), 
                 start_timestamp.to_string().bright_white());
     }
 
-    let mut page = 1;
-    let per_page = 200; // Max allowed by Strava
-    let mut total_distance = 0.0;
-    let mut total_activities = 0;
+    let query = ActivityQuery {
+        after: Some(start_timestamp),
+        before: end_date.map(|date| strava::day_timestamp(date + chrono::Days::new(1))),
+        concurrency,
+        verbose,
+    };
+    let activities = strava::activities(client, token, query);
+    futures::pin_mut!(activities);
+
+    let mut included = Vec::new();
     let mut filtered_activities = 0;
     
-    loop {
-        let response = client
-            .get("https://www.strava.com/api/v3/athlete/activities")
-            .header("Authorization", format!("Bearer {token}"))
-            .query(&[
-                ("after", start_timestamp.to_string()),
-                ("page", page.to_string()),
-                ("per_page", per_page.to_string()),
-            ])
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(anyhow::anyhow!("Strava API error: {error_text}"));
-        }
-
-        let activities: Vec<Activity> = response.json().await?;
-
-        if activities.is_empty() {
-            break;
-        }
-
-        if verbose {
-            println!("{} {} activities from page {}", 
-                     "📄 Fetched".cyan(),
-                     activities.len().to_string().bright_white().bold(),
-                     page.to_string().bright_white().bold());
-        }
-
-        for activity in &activities {
-            if allowed_types.contains(&activity.activity_type) {
-                total_distance += activity.distance;
-                total_activities += 1;
-
-                if verbose {
-                    println!(
-                        "  {} {}: {} km ({})",
-                        "✓".bright_green().bold(),
-                        activity.name.bright_white(),
-                        format!("{:.2}", activity.distance / 1000.0).bright_green().bold(),
-                        activity.activity_type.bright_blue()
-                    );
-                }
-            } else {
-                filtered_activities += 1;
-                if verbose {
-                    println!(
-                        "  {} {}: {} km ({}) - {}",
-                        "✗".bright_red().bold(),
-                        activity.name.dimmed(),
-                        format!("{:.2}", activity.distance / 1000.0).dimmed(),
-                        activity.activity_type.red(),
-                        "filtered out".red().italic()
-                    );
-                }
+    while let Some(activity) = activities.try_next().await? {
+        if allowed_types.contains(&activity.activity_type) {
+            if verbose {
+                println!(
+                    "  {} {}: {} km ({})",
+                    "✓".bright_green().bold(),
+                    activity.name.bright_white(),
+                    format!("{:.2}", activity.distance / 1000.0).bright_green().bold(),
+                    activity.activity_type.bright_blue()
+                );
+            }
+            included.push(activity);
+        } else {
+            filtered_activities += 1;
+            if verbose {
+                println!(
+                    "  {} {}: {} km ({}) - {}",
+                    "✗".bright_red().bold(),
+                    activity.name.dimmed(),
+                    format!("{:.2}", activity.distance / 1000.0).dimmed(),
+                    activity.activity_type.red(),
+                    "filtered out".red().italic()
+                );
             }
         }
-
-        // If we got fewer activities than requested, we've reached the end
-        if activities.len() < per_page {
-            break;
-        }
-
-        page += 1;
     }
 
     if verbose {
         println!();
         println!("{} {}", "📊 Total activities included:".bright_green().bold(), 
-                total_activities.to_string().bright_green().bold());
+                included.len().to_string().bright_green().bold());
         println!("{} {}", "🚫 Total activities filtered out:".bright_red().bold(), 
                 filtered_activities.to_string().bright_red().bold());
         println!();
     }
 
-    // Convert from meters to kilometers
-    Ok(total_distance / 1000.0)
+    Ok(included)
 }
 
 /// Parse activity types from user input, supporting shortcuts like 'cycling' and 'running'
@@ -492,26 +1435,50 @@ mod tests {
         assert!(result.is_err());
     }
 
+    fn redirect_uri() -> Url {
+        Url::parse(REDIRECT_URI).unwrap()
+    }
+
     #[test]
     fn test_build_auth_url() {
         let client_id = "12345";
         let state = "test-state";
-        let url = build_auth_url(client_id, state).unwrap();
+        let url = build_auth_url(client_id, state, credentials::DEFAULT_SCOPE, &redirect_uri()).unwrap();
 
         assert!(url.contains("client_id=12345"));
         assert!(url.contains("response_type=code"));
         assert!(url.contains("redirect_uri=http%3A%2F%2Flocalhost%2Fexchange_token"));
         assert!(url.contains("state=test-state"));
-        assert!(url.contains("scope=read%2Cactivity%3Aread_all"));
+        assert!(url.contains(
+            "scope=read%2Cactivity%3Aread_all%2Cprofile%3Aread_all%2Cactivity%3Awrite"
+        ));
+    }
+
+    #[test]
+    fn test_build_auth_url_custom_scope() {
+        let url = build_auth_url("12345", "test-state", "read,activity:read", &redirect_uri()).unwrap();
+        assert!(url.contains("scope=read%2Cactivity%3Aread&"));
     }
 
     #[test]
     fn test_extract_auth_code_success() {
         let redirect_url = "http://localhost/exchange_token?state=test-state&code=abc123&scope=read,activity:read_all";
         let state = "test-state";
-        let result = extract_auth_code(redirect_url, state);
+        let result = extract_auth_code(redirect_url, state, &redirect_uri());
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "abc123");
+        let redirect = result.unwrap();
+        assert_eq!(redirect.code, "abc123");
+        assert_eq!(
+            redirect.scopes,
+            Some(vec!["read".to_string(), "activity:read_all".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_extract_auth_code_without_scope() {
+        let redirect_url = "http://localhost/exchange_token?state=test-state&code=abc123";
+        let redirect = extract_auth_code(redirect_url, "test-state", &redirect_uri()).unwrap();
+        assert_eq!(redirect.scopes, None);
     }
 
     #[test]
@@ -519,7 +1486,7 @@ mod tests {
         let redirect_url =
             "http://localhost/exchange_token?state=test-state&scope=read,activity:read_all";
         let state = "test-state";
-        let result = extract_auth_code(redirect_url, state);
+        let result = extract_auth_code(redirect_url, state, &redirect_uri());
         assert!(result.is_err());
     }
 
@@ -527,15 +1494,39 @@ mod tests {
     fn test_extract_auth_code_state_mismatch() {
         let redirect_url = "http://localhost/exchange_token?state=wrong-state&code=abc123";
         let state = "test-state";
-        let result = extract_auth_code(redirect_url, state);
+        let result = extract_auth_code(redirect_url, state, &redirect_uri());
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_extract_auth_code_missing_state() {
+        let redirect_url = "http://localhost/exchange_token?code=abc123";
+        let error = extract_auth_code(redirect_url, "test-state", &redirect_uri()).unwrap_err();
+        assert!(error.to_string().contains("No state parameter"));
+    }
+
+    #[test]
+    fn test_extract_auth_code_checks_redirect_uri() {
+        for redirect_url in [
+            "http://evil.example/exchange_token?state=test-state&code=abc123",
+            "http://localhost/other?state=test-state&code=abc123",
+            "http://localhost:8080/exchange_token?state=test-state&code=abc123",
+            "https://localhost/exchange_token?state=test-state&code=abc123",
+        ] {
+            let error = extract_auth_code(redirect_url, "test-state", &redirect_uri()).unwrap_err();
+            assert!(error.to_string().contains("doesn't match the redirect URI"), "{redirect_url}");
+        }
+
+        let custom = Url::parse("http://127.0.0.1:5000/callback").unwrap();
+        let redirect_url = "http://127.0.0.1:5000/callback?state=test-state&code=abc123";
+        assert!(extract_auth_code(redirect_url, "test-state", &custom).is_ok());
+    }
+
     #[test]
     fn test_extract_auth_code_with_error() {
         let redirect_url = "http://localhost/exchange_token?error=access_denied&state=test-state";
         let state = "test-state";
-        let result = extract_auth_code(redirect_url, state);
+        let result = extract_auth_code(redirect_url, state, &redirect_uri());
         assert!(result.is_err());
     }
     
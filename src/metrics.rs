@@ -0,0 +1,251 @@
+use crate::cache::ActivityCache;
+use crate::chain::{self, ChainEvent};
+use crate::component::Component;
+use crate::config::Config;
+use crate::credentials::Profile;
+use crate::strava::Activity;
+use anyhow::{Context, Result};
+use colored::*;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+/// Totals of one activity type, in base units as Prometheus expects
+#[derive(Debug, Default, Clone, PartialEq)]
+struct TypeTotals {
+    count: usize,
+    distance_m: f64,
+    moving_s: f64,
+    elevation_m: f64,
+}
+
+fn totals_by_type(activities: &[Activity]) -> BTreeMap<&str, TypeTotals> {
+    let mut totals: BTreeMap<&str, TypeTotals> = BTreeMap::new();
+    for activity in activities {
+        let entry = totals.entry(activity.activity_type.as_str()).or_default();
+        entry.count += 1;
+        entry.distance_m += activity.distance;
+        entry.moving_s += activity.moving_time as f64;
+        entry.elevation_m += activity.total_elevation_gain;
+    }
+    totals
+}
+
+/// Escape a label value as required by the exposition format
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Render `{name="value",...}`, or nothing if there are no labels
+fn labels(pairs: &[(&str, &str)]) -> String {
+    if pairs.is_empty() {
+        return String::new();
+    }
+    let pairs: Vec<String> = pairs
+        .iter()
+        .map(|(name, value)| format!("{name}=\"{}\"", escape_label(value)))
+        .collect();
+    format!("{{{}}}", pairs.join(","))
+}
+
+/// A metric family: its HELP and TYPE lines followed by one sample per label set
+fn family(out: &mut String, name: &str, kind: &str, help: &str, samples: &[(String, f64)]) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+    for (labels, value) in samples {
+        let _ = writeln!(out, "{name}{labels} {value}");
+    }
+}
+
+/// Render activity totals per type and component wear in the Prometheus text
+/// exposition format. Series are labelled with `athlete` when it's known.
+pub fn render(
+    athlete: Option<i64>,
+    cache: &ActivityCache,
+    components: &[Component],
+    events: &[ChainEvent],
+) -> String {
+    let athlete = athlete.map(|id| id.to_string());
+    let athlete_label: Vec<(&str, &str)> = athlete
+        .as_deref()
+        .map(|id| vec![("athlete", id)])
+        .unwrap_or_default();
+
+    let totals = totals_by_type(&cache.activities);
+    let per_type = |value: fn(&TypeTotals) -> f64| -> Vec<(String, f64)> {
+        totals
+            .iter()
+            .map(|(activity_type, totals)| {
+                let mut pairs = athlete_label.clone();
+                pairs.push(("type", activity_type));
+                (labels(&pairs), value(totals))
+            })
+            .collect()
+    };
+
+    // Gauges rather than counters: reconciling or a webhook delete can shrink
+    // the cache, which Prometheus would take for a counter reset
+    let mut out = String::new();
+    family(
+        &mut out,
+        "chain_life_activities",
+        "gauge",
+        "Number of cached activities.",
+        &per_type(|t| t.count as f64),
+    );
+    family(
+        &mut out,
+        "chain_life_distance_meters",
+        "gauge",
+        "Distance of cached activities in meters.",
+        &per_type(|t| t.distance_m),
+    );
+    family(
+        &mut out,
+        "chain_life_moving_time_seconds",
+        "gauge",
+        "Moving time of cached activities in seconds.",
+        &per_type(|t| t.moving_s),
+    );
+    family(
+        &mut out,
+        "chain_life_elevation_gain_meters",
+        "gauge",
+        "Elevation gain of cached activities in meters.",
+        &per_type(|t| t.elevation_m),
+    );
+
+    let per_component = |value: &dyn Fn(&Component) -> f64| -> Vec<(String, f64)> {
+        components
+            .iter()
+            .map(|component| {
+                let mut pairs = athlete_label.clone();
+                pairs.push(("component", &component.name));
+                pairs.push(("kind", &component.kind));
+                (labels(&pairs), value(component))
+            })
+            .collect()
+    };
+    family(
+        &mut out,
+        "chain_life_component_distance_meters",
+        "gauge",
+        "Distance ridden on a component since it was installed, in meters.",
+        &per_component(&|c| chain::ridden_km(c, events, &cache.activities) * 1000.0),
+    );
+    family(
+        &mut out,
+        "chain_life_component_wear_percent",
+        "gauge",
+        "Percentage of a component's rated distance used up.",
+        &per_component(&|c| chain::component_wear(c, events, &cache.activities) * 100.0),
+    );
+
+    if let Some(synced_at) = cache.synced_at {
+        family(
+            &mut out,
+            "chain_life_last_sync_timestamp_seconds",
+            "gauge",
+            "Unix time of the last successful sync.",
+            &[(labels(&athlete_label), synced_at as f64)],
+        );
+    }
+    out
+}
+
+/// Render metrics from the local cache, config and the saved athlete profile
+pub fn render_current() -> Result<String> {
+    let athlete = Profile::load()?.map(|profile| profile.athlete_id);
+    let cache = ActivityCache::load()?;
+    let config = Config::load()?;
+    Ok(render(
+        athlete,
+        &cache,
+        &config.components,
+        &chain::load_chain_events()?,
+    ))
+}
+
+/// Write `content` next to `path` and rename it into place, so a textfile
+/// collector never reads a partially written file
+fn write_atomically(path: &Path, content: &str) -> Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+
+    std::fs::write(&tmp, content).with_context(|| format!("Failed to write {}", tmp.display()))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+pub fn handle_export_metrics(out: Option<PathBuf>) -> Result<()> {
+    let metrics = render_current()?;
+    match out {
+        Some(path) => {
+            write_atomically(&path, &metrics)?;
+            println!(
+                "{} {}",
+                "✅ Metrics written to".bright_green().bold(),
+                path.display().to_string().bright_white()
+            );
+        }
+        None => print!("{metrics}"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{activity, chain, of_type};
+
+    fn cache() -> ActivityCache {
+        ActivityCache {
+            synced_at: Some(1_700_000_000),
+            activities: vec![
+                activity(1, "2024-02-01", 20.0),
+                Activity {
+                    moving_time: 1800,
+                    ..activity(2, "2024-02-02", 10.0)
+                },
+                of_type(activity(3, "2024-02-03", 5.0), "Run"),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_escape_label() {
+        assert_eq!(escape_label(r#"Chain "A"\B"#), r#"Chain \"A\"\\B"#);
+        assert_eq!(escape_label("a\nb"), "a\\nb");
+    }
+
+    #[test]
+    fn test_render() {
+        let components = vec![chain("2024-01-01", 100.0)];
+        let metrics = render(Some(42), &cache(), &components, &[]);
+
+        assert!(metrics.contains("# TYPE chain_life_distance_meters gauge\n"));
+        assert!(
+            metrics.contains("chain_life_distance_meters{athlete=\"42\",type=\"Ride\"} 30000\n")
+        );
+        assert!(metrics.contains("chain_life_activities{athlete=\"42\",type=\"Run\"} 1\n"));
+        assert!(
+            metrics.contains("chain_life_moving_time_seconds{athlete=\"42\",type=\"Ride\"} 5400\n")
+        );
+        assert!(metrics.contains(
+            "chain_life_component_wear_percent{athlete=\"42\",component=\"KMC X11\",kind=\"chain\"} 30\n"
+        ));
+        assert!(
+            metrics.contains("chain_life_last_sync_timestamp_seconds{athlete=\"42\"} 1700000000\n")
+        );
+    }
+
+    #[test]
+    fn test_render_without_athlete() {
+        let metrics = render(None, &cache(), &[], &[]);
+        assert!(metrics.contains("chain_life_distance_meters{type=\"Run\"} 5000\n"));
+        assert!(metrics.contains("chain_life_last_sync_timestamp_seconds 1700000000\n"));
+    }
+}
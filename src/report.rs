@@ -0,0 +1,436 @@
+use crate::activity::Totals;
+use crate::chart::{self, Period};
+use crate::credentials;
+use crate::export;
+use crate::strava::{self, Activity};
+use anyhow::Result;
+use chrono::{Datelike, NaiveDate, Utc};
+use clap::ValueEnum;
+use colored::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::path::PathBuf;
+
+const CHART_WIDTH: f64 = 800.0;
+const CHART_HEIGHT: f64 = 240.0;
+const CHART_MARGIN: f64 = 40.0;
+
+/// Output formats for `report`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    /// Self-contained HTML page with tables and inline SVG charts
+    Html,
+    /// The report data as JSON
+    Json,
+}
+
+/// Season summary over a date range
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Report {
+    pub title: String,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub activity_types: String,
+    pub totals: Totals,
+    pub months: Vec<GroupTotals>,
+    pub gear: Vec<GroupTotals>,
+}
+
+/// Totals for one month or one piece of gear
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct GroupTotals {
+    pub label: String,
+    pub totals: Totals,
+}
+
+/// Summarise `activities` per month (including empty months) and per gear,
+/// biggest distance first. `gear_names` maps gear IDs to display names.
+pub fn build_report(
+    title: String,
+    start: NaiveDate,
+    end: NaiveDate,
+    activity_types: String,
+    activities: &[Activity],
+    gear_names: &HashMap<String, String>,
+) -> Report {
+    let mut by_month: HashMap<String, Vec<&Activity>> = HashMap::new();
+    let mut by_gear: HashMap<Option<String>, Vec<&Activity>> = HashMap::new();
+    for activity in activities {
+        if let Some(date) = activity.date() {
+            by_month
+                .entry(date.format("%Y-%m").to_string())
+                .or_default()
+                .push(activity);
+        }
+        by_gear
+            .entry(activity.gear_id.clone())
+            .or_default()
+            .push(activity);
+    }
+
+    let months = chart::bucket_distances(&BTreeMap::new(), Period::Month, start, end)
+        .into_iter()
+        .map(|(label, _)| GroupTotals {
+            totals: Totals::of(by_month.get(&label).into_iter().flatten().copied()),
+            label,
+        })
+        .collect();
+
+    let mut gear: Vec<GroupTotals> = by_gear
+        .into_iter()
+        .map(|(gear_id, activities)| GroupTotals {
+            label: match gear_id {
+                Some(id) => gear_names.get(&id).cloned().unwrap_or(id),
+                None => "Unassigned".to_string(),
+            },
+            totals: Totals::of(activities),
+        })
+        .collect();
+    gear.sort_by(|a, b| b.totals.distance_km.total_cmp(&a.totals.distance_km));
+
+    Report {
+        title,
+        start,
+        end,
+        activity_types,
+        totals: Totals::of(activities),
+        months,
+        gear,
+    }
+}
+
+/// Render the report as a standalone HTML page
+pub fn render_html(report: &Report, daily: &BTreeMap<NaiveDate, f64>) -> String {
+    let mut html = String::new();
+    let title = escape(&report.title);
+
+    let _ = write!(
+        html,
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body {{ font-family: system-ui, sans-serif; max-width: 900px; margin: 2em auto; padding: 0 1em; color: #222; }}
+h1 {{ margin-bottom: 0; }}
+.period {{ color: #666; margin-top: 0.2em; }}
+.totals {{ display: flex; gap: 1em; flex-wrap: wrap; }}
+.total {{ background: #f4f6f8; border-radius: 8px; padding: 0.8em 1.2em; }}
+.total .value {{ font-size: 1.6em; font-weight: bold; color: #fc4c02; }}
+table {{ border-collapse: collapse; width: 100%; margin: 1em 0; }}
+th, td {{ padding: 0.4em 0.8em; border-bottom: 1px solid #ddd; }}
+th {{ text-align: left; }}
+td.num, th.num {{ text-align: right; }}
+svg {{ width: 100%; height: auto; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<p class="period">{} to {} · {}</p>
+"#,
+        report.start,
+        report.end,
+        escape(&report.activity_types)
+    );
+
+    let totals = &report.totals;
+    let _ = write!(
+        html,
+        r#"<div class="totals">
+<div class="total"><div class="value">{:.1} km</div>Distance</div>
+<div class="total"><div class="value">{}</div>Activities</div>
+<div class="total"><div class="value">{:.1} h</div>Moving time</div>
+<div class="total"><div class="value">{:.0} m</div>Elevation gain</div>
+</div>
+"#,
+        totals.distance_km, totals.count, totals.moving_hours, totals.elevation_m
+    );
+
+    let bars: Vec<(String, f64)> = report
+        .months
+        .iter()
+        .map(|month| (month.label.clone(), month.totals.distance_km))
+        .collect();
+    html.push_str("<h2>Distance per month</h2>\n");
+    html.push_str(&svg_bar_chart(&bars));
+    html.push_str("<h2>Cumulative distance</h2>\n");
+    html.push_str(&svg_cumulative_chart(daily, report.start, report.end));
+
+    html.push_str("<h2>Per month</h2>\n");
+    html.push_str(&html_table("Month", &report.months));
+    html.push_str("<h2>Per gear</h2>\n");
+    html.push_str(&html_table("Gear", &report.gear));
+
+    let _ = write!(
+        html,
+        "<p class=\"period\">Generated by chain-life on {}</p>\n</body>\n</html>\n",
+        Utc::now().format("%Y-%m-%d %H:%M UTC")
+    );
+    html
+}
+
+fn html_table(label: &str, groups: &[GroupTotals]) -> String {
+    let mut html = format!(
+        "<table>\n<tr><th>{label}</th><th class=\"num\">Activities</th><th class=\"num\">Distance</th><th class=\"num\">Moving time</th><th class=\"num\">Elevation</th></tr>\n"
+    );
+    for group in groups {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{:.1} km</td><td class=\"num\">{:.1} h</td><td class=\"num\">{:.0} m</td></tr>",
+            escape(&group.label),
+            group.totals.count,
+            group.totals.distance_km,
+            group.totals.moving_hours,
+            group.totals.elevation_m
+        );
+    }
+    html.push_str("</table>\n");
+    html
+}
+
+/// Vertical bar chart with one labelled bar per entry
+fn svg_bar_chart(bars: &[(String, f64)]) -> String {
+    let mut svg = svg_open();
+    let max = bars.iter().map(|(_, value)| *value).fold(0.0, f64::max);
+    let plot_width = CHART_WIDTH - 2.0 * CHART_MARGIN;
+    let plot_height = CHART_HEIGHT - 2.0 * CHART_MARGIN;
+    let slot = plot_width / bars.len().max(1) as f64;
+
+    for (i, (label, value)) in bars.iter().enumerate() {
+        let height = if max > 0.0 {
+            value / max * plot_height
+        } else {
+            0.0
+        };
+        let x = CHART_MARGIN + i as f64 * slot;
+        let y = CHART_MARGIN + plot_height - height;
+        let _ = writeln!(
+            svg,
+            r##"<rect x="{:.1}" y="{y:.1}" width="{:.1}" height="{height:.1}" fill="#fc4c02"><title>{}: {value:.1} km</title></rect>"##,
+            x + slot * 0.1,
+            slot * 0.8,
+            escape(label)
+        );
+        let _ = writeln!(
+            svg,
+            r##"<text x="{:.1}" y="{:.1}" font-size="11" text-anchor="middle" fill="#666">{}</text>"##,
+            x + slot / 2.0,
+            CHART_HEIGHT - CHART_MARGIN + 16.0,
+            escape(label)
+        );
+    }
+
+    let _ = writeln!(
+        svg,
+        r##"<text x="{CHART_MARGIN}" y="{:.1}" font-size="11" fill="#666">{max:.0} km</text>"##,
+        CHART_MARGIN - 8.0
+    );
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Line chart of the running total distance from `start` to `end`
+fn svg_cumulative_chart(
+    daily: &BTreeMap<NaiveDate, f64>,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> String {
+    let mut svg = svg_open();
+    let days: Vec<NaiveDate> = start.iter_days().take_while(|day| *day <= end).collect();
+    let total: f64 = daily.range(start..=end).map(|(_, km)| km).sum();
+    let plot_width = CHART_WIDTH - 2.0 * CHART_MARGIN;
+    let plot_height = CHART_HEIGHT - 2.0 * CHART_MARGIN;
+    let step = plot_width / (days.len().max(2) - 1) as f64;
+
+    let mut running = 0.0;
+    let mut points = Vec::with_capacity(days.len());
+    for (i, day) in days.iter().enumerate() {
+        running += daily.get(day).copied().unwrap_or(0.0);
+        let y = if total > 0.0 {
+            running / total * plot_height
+        } else {
+            0.0
+        };
+        points.push(format!(
+            "{:.1},{:.1}",
+            CHART_MARGIN + i as f64 * step,
+            CHART_MARGIN + plot_height - y
+        ));
+    }
+
+    let _ = writeln!(
+        svg,
+        r##"<polyline points="{}" fill="none" stroke="#fc4c02" stroke-width="2"/>"##,
+        points.join(" ")
+    );
+    for (i, day) in days.iter().enumerate().filter(|(_, day)| day.day() == 1) {
+        let _ = writeln!(
+            svg,
+            r##"<text x="{:.1}" y="{:.1}" font-size="11" fill="#666">{}</text>"##,
+            CHART_MARGIN + i as f64 * step,
+            CHART_HEIGHT - CHART_MARGIN + 16.0,
+            day.format("%b")
+        );
+    }
+    let _ = writeln!(
+        svg,
+        r##"<text x="{CHART_MARGIN}" y="{:.1}" font-size="11" fill="#666">{total:.0} km</text>"##,
+        CHART_MARGIN - 8.0
+    );
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn svg_open() -> String {
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {CHART_WIDTH} {CHART_HEIGHT}\" role=\"img\">\n"
+    )
+}
+
+/// Escape text for use in HTML content and attributes
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_report(
+    format: ReportFormat,
+    out: Option<PathBuf>,
+    year: Option<i32>,
+    from: Option<String>,
+    to: Option<String>,
+    title: Option<String>,
+    token: Option<String>,
+    activity_types: String,
+    concurrency: usize,
+    verbose: bool,
+) -> Result<()> {
+    let today = Utc::now().date_naive();
+    let year = year.or((from.is_none() && to.is_none()).then(|| today.year()));
+    let (start, end) = crate::goal::goal_period(year, from, to)?;
+    let allowed_types = crate::parse_activity_types(&activity_types)?;
+    let token = credentials::access_token(token, &[credentials::READ_ALL_ACTIVITIES]).await?;
+
+    let activities = crate::fetch_activities_since(
+        start,
+        Some(end),
+        token.clone(),
+        allowed_types,
+        concurrency,
+        verbose,
+    )
+    .await?;
+
+    // Resolve gear names; unknown or inaccessible gear falls back to its ID
+    let client = reqwest::Client::new();
+    let mut gear_names = HashMap::new();
+    for gear_id in activities.iter().filter_map(|a| a.gear_id.as_ref()) {
+        if gear_names.contains_key(gear_id) {
+            continue;
+        }
+        match strava::get_gear(&client, &token, gear_id).await {
+            Ok(gear) => {
+                gear_names.insert(gear_id.clone(), gear.name.unwrap_or(gear.id));
+            }
+            Err(err) => {
+                if verbose {
+                    println!("{} {gear_id}: {err}", "⚠️ Could not fetch gear".yellow());
+                }
+                gear_names.insert(gear_id.clone(), gear_id.clone());
+            }
+        }
+    }
+
+    let title = title.unwrap_or_else(|| format!("Season summary {start} to {end}"));
+    let report = build_report(title, start, end, activity_types, &activities, &gear_names);
+    let content = match format {
+        ReportFormat::Html => render_html(&report, &chart::daily_distances(&activities)),
+        ReportFormat::Json => serde_json::to_string_pretty(&report)?,
+    };
+
+    match out {
+        Some(path) => {
+            export::write_file(&path, &content)?;
+            println!(
+                "{} {}",
+                "📄 Report written to".bright_green().bold(),
+                path.display().to_string().bright_white().bold()
+            );
+        }
+        None => print!("{content}"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{activity, on_gear};
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn report() -> Report {
+        let activities = vec![
+            on_gear(activity(1, "2025-01-05", 40.0), "b1"),
+            on_gear(activity(2, "2025-03-02", 60.0), "b1"),
+            activity(3, "2025-03-09", 20.0),
+        ];
+        let names = HashMap::from([("b1".to_string(), "Road <bike>".to_string())]);
+        build_report(
+            "2025".to_string(),
+            date(2025, 1, 1),
+            date(2025, 3, 31),
+            "cycling".to_string(),
+            &activities,
+            &names,
+        )
+    }
+
+    #[test]
+    fn test_build_report_groups() {
+        let report = report();
+        assert_eq!(report.totals.count, 3);
+        assert_eq!(report.totals.distance_km, 120.0);
+
+        let months: Vec<(&str, f64)> = report
+            .months
+            .iter()
+            .map(|m| (m.label.as_str(), m.totals.distance_km))
+            .collect();
+        assert_eq!(
+            months,
+            vec![("2025-01", 40.0), ("2025-02", 0.0), ("2025-03", 80.0)]
+        );
+
+        assert_eq!(report.gear[0].label, "Road <bike>");
+        assert_eq!(report.gear[0].totals.distance_km, 100.0);
+        assert_eq!(report.gear[1].label, "Unassigned");
+    }
+
+    #[test]
+    fn test_render_html_is_self_contained_and_escaped() {
+        let html = render_html(&report(), &BTreeMap::new());
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<svg"));
+        assert!(html.contains("Road &lt;bike&gt;"));
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("<link"));
+        assert!(!html.contains("src="));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape(r#"<a href="x">&'"#),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&#39;"
+        );
+    }
+}
@@ -0,0 +1,268 @@
+use crate::activity::Totals;
+use crate::cache::{self, ActivityCache};
+use crate::chain::{self, ChainEvent};
+use crate::config::Config;
+use crate::credentials;
+use crate::strava::Activity;
+use anyhow::{Context, Result};
+use axum::extract::Query;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::NaiveDate;
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Query parameters accepted by `/totals` and `/activities`
+#[derive(Deserialize, Debug, Default)]
+pub struct FilterParams {
+    /// First day to include, YYYY-MM-DD
+    pub after: Option<String>,
+    /// Day to stop before, YYYY-MM-DD
+    pub before: Option<String>,
+    /// Activity types (comma-separated), as for 'fetch'
+    pub types: Option<String>,
+}
+
+/// Parsed and validated [`FilterParams`]
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct Filter {
+    pub after: Option<NaiveDate>,
+    pub before: Option<NaiveDate>,
+    pub types: Option<Vec<String>>,
+}
+
+impl Filter {
+    pub fn parse(params: &FilterParams) -> Result<Self> {
+        let date = |value: &Option<String>| value.as_deref().map(crate::parse_date).transpose();
+        Ok(Self {
+            after: date(&params.after)?,
+            before: date(&params.before)?,
+            types: params
+                .types
+                .as_deref()
+                .map(crate::parse_activity_types)
+                .transpose()?,
+        })
+    }
+
+    fn apply<'a>(&self, cache: &'a ActivityCache) -> Vec<&'a Activity> {
+        cache.query(self.after, self.before, self.types.as_deref())
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct TotalsResponse {
+    #[serde(flatten)]
+    filter: Filter,
+    #[serde(flatten)]
+    totals: Totals,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct ComponentStatus {
+    name: String,
+    kind: String,
+    installed: NaiveDate,
+    rated_km: f64,
+    distance_km: f64,
+    wear: f64,
+}
+
+/// Error returned to API clients as `{"error": "..."}`
+struct ApiError(StatusCode, String);
+
+impl ApiError {
+    fn bad_request(error: anyhow::Error) -> Self {
+        Self(StatusCode::BAD_REQUEST, format!("{error:#}"))
+    }
+
+    fn internal(error: anyhow::Error) -> Self {
+        Self(StatusCode::INTERNAL_SERVER_ERROR, format!("{error:#}"))
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(serde_json::json!({ "error": self.1 }))).into_response()
+    }
+}
+
+async fn totals(Query(params): Query<FilterParams>) -> Result<Json<TotalsResponse>, ApiError> {
+    let filter = Filter::parse(&params).map_err(ApiError::bad_request)?;
+    let cache = ActivityCache::load().map_err(ApiError::internal)?;
+    let totals = Totals::of(filter.apply(&cache));
+    Ok(Json(TotalsResponse { filter, totals }))
+}
+
+async fn activities(Query(params): Query<FilterParams>) -> Result<Json<Vec<Activity>>, ApiError> {
+    let filter = Filter::parse(&params).map_err(ApiError::bad_request)?;
+    let cache = ActivityCache::load().map_err(ApiError::internal)?;
+    Ok(Json(filter.apply(&cache).into_iter().cloned().collect()))
+}
+
+async fn components() -> Result<Json<Vec<ComponentStatus>>, ApiError> {
+    let config = Config::load().map_err(ApiError::internal)?;
+    let cache = ActivityCache::load().map_err(ApiError::internal)?;
+    let events = chain::load_chain_events().map_err(ApiError::internal)?;
+    Ok(Json(component_statuses(
+        &config,
+        &events,
+        &cache.activities,
+    )))
+}
+
+fn component_statuses(
+    config: &Config,
+    events: &[ChainEvent],
+    activities: &[Activity],
+) -> Vec<ComponentStatus> {
+    config
+        .components
+        .iter()
+        .map(|component| {
+            let distance_km = chain::ridden_km(component, events, activities);
+            ComponentStatus {
+                name: component.name.clone(),
+                kind: component.kind.clone(),
+                installed: component.installed,
+                rated_km: component.rated_km,
+                distance_km,
+                wear: component.wear_at(distance_km),
+            }
+        })
+        .collect()
+}
+
+async fn metrics() -> Result<Response, ApiError> {
+    let metrics = crate::metrics::render_current().map_err(ApiError::internal)?;
+    Ok((
+        [(
+            axum::http::header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        metrics,
+    )
+        .into_response())
+}
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/totals", get(totals))
+        .route("/activities", get(activities))
+        .route("/components", get(components))
+        .route("/metrics", get(metrics))
+}
+
+/// Sync the cache every `minutes`, logging failures instead of stopping the server
+async fn sync_periodically(minutes: u64, concurrency: usize) {
+    let mut interval = tokio::time::interval(Duration::from_secs(minutes * 60));
+    loop {
+        interval.tick().await;
+        let result = async {
+            let token =
+                credentials::access_token(None, &[credentials::READ_ALL_ACTIVITIES]).await?;
+            let mut cache = ActivityCache::load()?;
+            let reconcile_days = cache::reconcile_days(None)?;
+            let summary =
+                cache::sync(&mut cache, token, concurrency, reconcile_days, false).await?;
+            cache.save()?;
+            crate::alert::check(&Config::load()?, &cache.activities).await?;
+            anyhow::Ok(summary)
+        }
+        .await;
+
+        match result {
+            Ok(summary) => println!(
+                "{} {} new, {} updated, {} deleted",
+                "🔄 Synced".cyan(),
+                summary.added.to_string().bright_white(),
+                summary.updated.to_string().bright_white(),
+                summary.removed.to_string().bright_white()
+            ),
+            Err(error) => eprintln!("{} {error:#}", "⚠️  Sync failed:".yellow().bold()),
+        }
+    }
+}
+
+pub async fn handle_serve(
+    addr: String,
+    sync_interval: Option<u64>,
+    concurrency: usize,
+) -> Result<()> {
+    if let Some(minutes) = sync_interval {
+        if minutes == 0 {
+            return Err(anyhow::anyhow!("--sync-interval must be at least 1 minute"));
+        }
+        tokio::spawn(sync_periodically(minutes, concurrency));
+    }
+
+    let listener = tokio::net::TcpListener::bind(&addr)
+        .await
+        .with_context(|| format!("Failed to listen on {addr}"))?;
+
+    println!(
+        "{} {}",
+        "🌐 Serving on".bright_cyan().bold(),
+        format!("http://{}", listener.local_addr()?)
+            .blue()
+            .underline()
+    );
+    println!("{}", "   GET /totals?after=&before=&types=".dimmed());
+    println!("{}", "   GET /activities?after=&before=&types=".dimmed());
+    println!("{}", "   GET /components".dimmed());
+    println!("{}", "   GET /metrics".dimmed());
+
+    axum::serve(listener, router()).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{activity, chain};
+
+    #[test]
+    fn test_filter_parse() {
+        let filter = Filter::parse(&FilterParams {
+            after: Some("2024-01-01".to_string()),
+            before: None,
+            types: Some("Run,Ride".to_string()),
+        })
+        .unwrap();
+        assert_eq!(
+            filter,
+            Filter {
+                after: NaiveDate::from_ymd_opt(2024, 1, 1),
+                before: None,
+                types: Some(vec!["Run".to_string(), "Ride".to_string()]),
+            }
+        );
+
+        assert_eq!(
+            Filter::parse(&FilterParams::default()).unwrap(),
+            Filter::default()
+        );
+        assert!(Filter::parse(&FilterParams {
+            before: Some("yesterday".to_string()),
+            ..Default::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_component_statuses() {
+        let config = Config {
+            components: vec![chain("2024-01-01", 100.0)],
+            ..Default::default()
+        };
+        let activities = vec![activity(1, "2024-02-01", 25.0)];
+
+        let statuses = component_statuses(&config, &[], &activities);
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(statuses[0].distance_km, 25.0);
+        assert_eq!(statuses[0].wear, 0.25);
+    }
+}
@@ -0,0 +1,200 @@
+use crate::credentials;
+use crate::store;
+use crate::strava::Activity;
+use anyhow::Result;
+use chrono::{NaiveDate, Utc};
+use colored::*;
+use serde::{Deserialize, Serialize};
+
+/// A maintenance job done on a bike
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ServiceEvent {
+    pub id: u32,
+    pub date: NaiveDate,
+    /// Strava gear ID of the bike
+    pub bike: String,
+    /// What was done, e.g. "chain replaced" or "brake bleed"
+    pub kind: String,
+    pub cost: Option<f64>,
+    pub notes: Option<String>,
+}
+
+pub fn load_events() -> Result<Vec<ServiceEvent>> {
+    store::load("services")
+}
+
+/// Distance ridden on `bike` from each event's day up to the next event's day
+/// (or up to now for the last one). `events` must be sorted by date.
+pub fn distances_after(events: &[&ServiceEvent], activities: &[Activity], bike: &str) -> Vec<f64> {
+    events
+        .iter()
+        .enumerate()
+        .map(|(i, event)| {
+            let next = events.get(i + 1).map(|next| next.date);
+            activities
+                .iter()
+                .filter(|a| a.gear_id.as_deref() == Some(bike))
+                .filter(|a| {
+                    a.date().is_some_and(|date| {
+                        date >= event.date && next.is_none_or(|next| date < next)
+                    })
+                })
+                .map(|a| a.distance / 1000.0)
+                .sum()
+        })
+        .collect()
+}
+
+pub fn handle_add(
+    bike: String,
+    kind: String,
+    date: Option<String>,
+    cost: Option<f64>,
+    notes: Option<String>,
+) -> Result<()> {
+    if cost.is_some_and(|cost| cost < 0.0) {
+        return Err(anyhow::anyhow!("Cost can't be negative"));
+    }
+    let date = match date {
+        Some(date) => crate::parse_date(&date)?,
+        None => Utc::now().date_naive(),
+    };
+
+    let mut events = load_events()?;
+    let id = events.iter().map(|event| event.id).max().unwrap_or(0) + 1;
+    events.push(ServiceEvent {
+        id,
+        date,
+        bike: bike.clone(),
+        kind: kind.clone(),
+        cost,
+        notes,
+    });
+    events.sort_by_key(|event| event.date);
+    store::save("services", &events)?;
+
+    println!(
+        "{} #{} {} on {} ({})",
+        "🔧 Service logged:".bright_green().bold(),
+        id,
+        kind.bright_white().bold(),
+        bike.bright_blue(),
+        date
+    );
+    Ok(())
+}
+
+pub fn handle_remove(id: u32) -> Result<()> {
+    let mut events = load_events()?;
+    let before = events.len();
+    events.retain(|event| event.id != id);
+    if events.len() == before {
+        return Err(anyhow::anyhow!("No service event #{id}"));
+    }
+    store::save("services", &events)?;
+
+    println!(
+        "{} #{}",
+        "🗑️ Service event removed:".bright_green().bold(),
+        id
+    );
+    Ok(())
+}
+
+pub async fn handle_history(
+    bike: Option<String>,
+    token: Option<String>,
+    activity_types: String,
+    concurrency: usize,
+    verbose: bool,
+) -> Result<()> {
+    let events: Vec<ServiceEvent> = load_events()?
+        .into_iter()
+        .filter(|event| bike.as_ref().is_none_or(|bike| &event.bike == bike))
+        .collect();
+    let Some(first) = events.first() else {
+        println!(
+            "{}",
+            "No service events logged. Use 'chain-life service add' to add one.".yellow()
+        );
+        return Ok(());
+    };
+
+    let token = credentials::access_token(token, &[credentials::READ_ALL_ACTIVITIES]).await?;
+    let allowed_types = crate::parse_activity_types(&activity_types)?;
+    let activities =
+        crate::fetch_activities_since(first.date, None, token, allowed_types, concurrency, verbose)
+            .await?;
+
+    let mut bikes: Vec<&str> = events.iter().map(|event| event.bike.as_str()).collect();
+    bikes.sort();
+    bikes.dedup();
+
+    for bike in bikes {
+        let bike_events: Vec<&ServiceEvent> =
+            events.iter().filter(|event| event.bike == bike).collect();
+        let distances = distances_after(&bike_events, &activities, bike);
+        let total_cost: f64 = bike_events.iter().filter_map(|event| event.cost).sum();
+
+        println!();
+        println!("{} {}", "🚲".bright_cyan(), bike.bright_white().bold());
+        for (event, km) in bike_events.iter().zip(&distances) {
+            let cost = event
+                .cost
+                .map(|cost| format!(" ({cost:.2})"))
+                .unwrap_or_default();
+            println!(
+                "  {} {} {}{}  {}",
+                event.date.to_string().bright_white(),
+                format!("#{}", event.id).dimmed(),
+                event.kind.bright_green().bold(),
+                cost.bright_yellow(),
+                event.notes.as_deref().unwrap_or_default().dimmed()
+            );
+            println!("      {} {}", "↓".dimmed(), format!("{km:.0} km").cyan());
+        }
+        println!(
+            "  {}  {} {}",
+            "Now".bright_white(),
+            "Total cost:".bright_yellow(),
+            format!("{total_cost:.2}").bright_yellow().bold()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{activity, day, on_gear};
+
+    fn event(id: u32, date: &str, bike: &str) -> ServiceEvent {
+        ServiceEvent {
+            id,
+            date: day(date),
+            bike: bike.to_string(),
+            kind: "chain replaced".to_string(),
+            cost: Some(30.0),
+            notes: None,
+        }
+    }
+
+    #[test]
+    fn test_distances_after() {
+        let first = event(1, "2024-01-01", "b1");
+        let second = event(2, "2024-02-01", "b1");
+        let activities = vec![
+            on_gear(activity(1, "2023-12-31", 5.0), "b1"),
+            on_gear(activity(2, "2024-01-01", 10.0), "b1"),
+            on_gear(activity(3, "2024-01-20", 20.0), "b1"),
+            on_gear(activity(4, "2024-01-21", 99.0), "b2"),
+            on_gear(activity(5, "2024-02-01", 40.0), "b1"),
+        ];
+
+        assert_eq!(
+            distances_after(&[&first, &second], &activities, "b1"),
+            vec![30.0, 40.0]
+        );
+        assert!(distances_after(&[], &activities, "b1").is_empty());
+    }
+}
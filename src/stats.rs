@@ -0,0 +1,297 @@
+use crate::activity::Totals;
+use crate::cache::ActivityCache;
+use crate::credentials::{self, Profile};
+use crate::strava::{self, Activity, ActivityStats, ActivityTotal};
+use anyhow::Result;
+use chrono::{Datelike, Days, NaiveDate, Utc};
+use colored::*;
+
+/// Days in Strava's "recent" totals, which cover the last four weeks
+const RECENT_DAYS: u64 = 28;
+
+/// The sports `/athletes/{id}/stats` has totals for
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Sport {
+    Ride,
+    Run,
+    Swim,
+}
+
+impl Sport {
+    const ALL: [Sport; 3] = [Sport::Ride, Sport::Run, Sport::Swim];
+
+    fn label(self) -> &'static str {
+        match self {
+            Sport::Ride => "🚴 Rides",
+            Sport::Run => "🏃 Runs",
+            Sport::Swim => "🏊 Swims",
+        }
+    }
+
+    /// Activity types counted towards the sport when cross-checking
+    fn activity_types(self) -> Result<Vec<String>> {
+        match self {
+            Sport::Ride => crate::parse_activity_types("cycling"),
+            Sport::Run => crate::parse_activity_types("running"),
+            Sport::Swim => crate::parse_activity_types("Swim"),
+        }
+    }
+}
+
+/// The periods `/athletes/{id}/stats` has totals for
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Window {
+    Recent,
+    YearToDate,
+    AllTime,
+}
+
+impl Window {
+    const ALL: [Window; 3] = [Window::Recent, Window::YearToDate, Window::AllTime];
+
+    fn label(self) -> &'static str {
+        match self {
+            Window::Recent => "🗓️ Last 4 weeks",
+            Window::YearToDate => "🗓️ This year",
+            Window::AllTime => "🗓️ All time",
+        }
+    }
+
+    /// First day the window covers, given `today`; `None` for all time
+    fn start(self, today: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            Window::Recent => today.checked_sub_days(Days::new(RECENT_DAYS - 1)),
+            Window::YearToDate => NaiveDate::from_ymd_opt(today.year(), 1, 1),
+            Window::AllTime => None,
+        }
+    }
+
+    fn totals(self, stats: &ActivityStats, sport: Sport) -> &ActivityTotal {
+        match (self, sport) {
+            (Window::Recent, Sport::Ride) => &stats.recent_ride_totals,
+            (Window::Recent, Sport::Run) => &stats.recent_run_totals,
+            (Window::Recent, Sport::Swim) => &stats.recent_swim_totals,
+            (Window::YearToDate, Sport::Ride) => &stats.ytd_ride_totals,
+            (Window::YearToDate, Sport::Run) => &stats.ytd_run_totals,
+            (Window::YearToDate, Sport::Swim) => &stats.ytd_swim_totals,
+            (Window::AllTime, Sport::Ride) => &stats.all_ride_totals,
+            (Window::AllTime, Sport::Run) => &stats.all_run_totals,
+            (Window::AllTime, Sport::Swim) => &stats.all_swim_totals,
+        }
+    }
+}
+
+impl From<&ActivityTotal> for Totals {
+    fn from(total: &ActivityTotal) -> Self {
+        Totals {
+            count: total.count.max(0) as usize,
+            distance_km: total.distance / 1000.0,
+            moving_hours: total.moving_time as f64 / 3600.0,
+            elevation_m: total.elevation_gain,
+        }
+    }
+}
+
+/// Totals of the public `activities` of the given `types` started on or after
+/// `since`, to compare with Strava's stats, which leave out everything else
+fn local_totals(activities: &[Activity], types: &[String], since: Option<NaiveDate>) -> Totals {
+    Totals::of(activities.iter().filter(|a| {
+        a.is_public()
+            && types.contains(&a.activity_type)
+            && since.is_none_or(|since| a.date().is_some_and(|date| date >= since))
+    }))
+}
+
+/// Whether locally computed totals agree with Strava's, allowing for rounding
+fn agrees(remote: &Totals, local: &Totals) -> bool {
+    let tolerance_km = (remote.distance_km * 0.005).max(0.1);
+    remote.count == local.count && (remote.distance_km - local.distance_km).abs() <= tolerance_km
+}
+
+fn format_totals(totals: &Totals) -> String {
+    format!(
+        "{:>5} activities {:>10.1} km {:>8.1} h {:>8.0} m",
+        totals.count, totals.distance_km, totals.moving_hours, totals.elevation_m
+    )
+}
+
+pub async fn handle_stats(token: Option<String>, check: bool) -> Result<()> {
+    let client = reqwest::Client::new();
+    let explicit = token.is_some();
+    let token = credentials::access_token(token, &[]).await?;
+    // The profile saved by 'auth' knows the athlete; a bare token doesn't
+    let saved_id = if explicit {
+        None
+    } else {
+        Profile::load()?.map(|profile| profile.athlete_id)
+    };
+    let athlete_id = match saved_id {
+        Some(id) => id,
+        None => strava::get_athlete(&client, &token).await?.id,
+    };
+    let stats = strava::get_athlete_stats(&client, &token, athlete_id).await?;
+
+    let cache = if check {
+        let cache = ActivityCache::load()?;
+        if cache.activities.is_empty() {
+            eprintln!(
+                "{}",
+                "⚠️  No cached activities to check against. Run 'chain-life sync' first.".yellow()
+            );
+        }
+        Some(cache)
+    } else {
+        None
+    };
+
+    println!(
+        "{} {}",
+        "📊 Strava totals for athlete".bright_cyan().bold(),
+        format!("#{athlete_id}").bright_white().bold()
+    );
+    let today = Utc::now().date_naive();
+    let mut mismatches = 0;
+    for window in Window::ALL {
+        println!();
+        println!("{}", window.label().bright_cyan().bold());
+        for sport in Sport::ALL {
+            let remote = Totals::from(window.totals(&stats, sport));
+            println!(
+                "  {:<9} {}",
+                sport.label(),
+                format_totals(&remote).bright_white()
+            );
+
+            if let Some(cache) = &cache {
+                let local = local_totals(
+                    &cache.activities,
+                    &sport.activity_types()?,
+                    window.start(today),
+                );
+                if agrees(&remote, &local) {
+                    println!("  {:<9} {}", "", "✅ matches the local cache".green());
+                } else {
+                    mismatches += 1;
+                    println!(
+                        "  {:<9} {}",
+                        "",
+                        format!("⚠️  local: {}", format_totals(&local)).yellow()
+                    );
+                }
+            }
+        }
+    }
+
+    println!();
+    if let Some(distance) = stats.biggest_ride_distance {
+        println!(
+            "{} {}",
+            "🏆 Longest ride:".bright_yellow().bold(),
+            format!("{:.1} km", distance / 1000.0).bright_white()
+        );
+    }
+    if let Some(elevation) = stats.biggest_climb_elevation_gain {
+        println!(
+            "{} {}",
+            "⛰️ Biggest climb:".bright_yellow().bold(),
+            format!("{elevation:.0} m").bright_white()
+        );
+    }
+
+    if mismatches > 0 {
+        println!();
+        println!(
+            "{}",
+            "💡 Strava's totals only count activities visible to Everyone, and the cache \
+             only has what was last synced, so private activities or a stale cache show \
+             up as differences."
+                .dimmed()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{activity, day, of_type};
+
+    #[test]
+    fn test_window_start() {
+        let today = day("2024-03-10");
+        assert_eq!(Window::Recent.start(today), Some(day("2024-02-12")));
+        assert_eq!(Window::YearToDate.start(today), Some(day("2024-01-01")));
+        assert_eq!(Window::AllTime.start(today), None);
+    }
+
+    #[test]
+    fn test_parse_stats() {
+        let stats: ActivityStats = serde_json::from_value(serde_json::json!({
+            "biggest_ride_distance": 180000.0,
+            "biggest_climb_elevation_gain": 1200.0,
+            "recent_ride_totals": {"count": 2, "distance": 90500.0, "moving_time": 10800,
+                "elapsed_time": 11000, "elevation_gain": 800.0, "achievement_count": 3},
+            "recent_run_totals": {"count": 0, "distance": 0.0, "moving_time": 0,
+                "elapsed_time": 0, "elevation_gain": 0.0},
+            "recent_swim_totals": {"count": 0, "distance": 0.0, "moving_time": 0,
+                "elapsed_time": 0, "elevation_gain": 0.0},
+            "ytd_ride_totals": {"count": 5, "distance": 300000, "moving_time": 36000,
+                "elapsed_time": 40000, "elevation_gain": 2500},
+            "ytd_run_totals": {"count": 0, "distance": 0, "moving_time": 0,
+                "elapsed_time": 0, "elevation_gain": 0},
+            "ytd_swim_totals": {"count": 0, "distance": 0, "moving_time": 0,
+                "elapsed_time": 0, "elevation_gain": 0},
+            "all_ride_totals": {"count": 5, "distance": 300000, "moving_time": 36000,
+                "elapsed_time": 40000, "elevation_gain": 2500},
+            "all_run_totals": {"count": 1, "distance": 5000, "moving_time": 1500,
+                "elapsed_time": 1600, "elevation_gain": 20},
+            "all_swim_totals": {"count": 0, "distance": 0, "moving_time": 0,
+                "elapsed_time": 0, "elevation_gain": 0}
+        }))
+        .unwrap();
+
+        let recent = Totals::from(Window::Recent.totals(&stats, Sport::Ride));
+        assert_eq!(recent.count, 2);
+        assert_eq!(recent.distance_km, 90.5);
+        assert_eq!(recent.moving_hours, 3.0);
+        assert_eq!(Window::AllTime.totals(&stats, Sport::Run).distance, 5000.0);
+    }
+
+    #[test]
+    fn test_local_totals_and_agreement() {
+        let activities = vec![
+            activity(1, "2023-12-31", 50.0),
+            of_type(activity(2, "2024-02-20", 30.0), "VirtualRide"),
+            activity(3, "2024-03-01", 20.0),
+            of_type(activity(4, "2024-03-02", 10.0), "Run"),
+            Activity {
+                visibility: Some("followers_only".to_string()),
+                ..activity(5, "2024-03-03", 40.0)
+            },
+            Activity {
+                private: true,
+                ..activity(6, "2024-03-04", 40.0)
+            },
+        ];
+        let rides = Sport::Ride.activity_types().unwrap();
+        let since = Window::YearToDate.start(day("2024-03-10"));
+
+        let local = local_totals(&activities, &rides, since);
+        assert_eq!(local.count, 2);
+        assert_eq!(local.distance_km, 50.0);
+        assert_eq!(local_totals(&activities, &rides, None).count, 3);
+
+        let remote = Totals {
+            count: 2,
+            distance_km: 50.2,
+            ..Default::default()
+        };
+        assert!(agrees(&remote, &local));
+        let ride_missing = Totals {
+            count: 1,
+            distance_km: 30.0,
+            ..Default::default()
+        };
+        assert!(!agrees(&ride_missing, &local));
+    }
+}
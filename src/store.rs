@@ -0,0 +1,85 @@
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Environment variable overriding the location of the data directory
+const DATA_ENV: &str = "CHAIN_LIFE_DATA";
+
+/// Directory holding local data: `$CHAIN_LIFE_DATA`, or `chain-life` in the
+/// platform's data directory
+pub fn data_dir() -> Result<PathBuf> {
+    if let Some(path) = std::env::var_os(DATA_ENV) {
+        return Ok(PathBuf::from(path));
+    }
+
+    let dir = dirs::data_dir().context("Could not determine the data directory")?;
+    Ok(dir.join("chain-life"))
+}
+
+/// Load `<name>.json` from the data directory, or the default value if it doesn't exist yet
+pub fn load<T: DeserializeOwned + Default>(name: &str) -> Result<T> {
+    let path = data_dir()?.join(format!("{name}.json"));
+    if !path.exists() {
+        return Ok(T::default());
+    }
+
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Save `value` as `<name>.json` in the data directory
+pub fn save<T: Serialize>(name: &str, value: &T) -> Result<()> {
+    let path = path(name)?;
+    let content = serde_json::to_string_pretty(value)?;
+    std::fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Remove `<name>.json` from the data directory, returning whether it existed
+pub fn remove(name: &str) -> Result<bool> {
+    let path = data_dir()?.join(format!("{name}.json"));
+    if !path.exists() {
+        return Ok(false);
+    }
+    std::fs::remove_file(&path).with_context(|| format!("Failed to remove {}", path.display()))?;
+    Ok(true)
+}
+
+/// Write `content` to `file_name` in the data directory, readable by the current user only
+pub fn write_private(file_name: &str, content: &[u8]) -> Result<()> {
+    write_private_file(&file_path(file_name)?, content)
+}
+
+/// Write `content` to `path`, readable by the current user only
+pub fn write_private_file(path: &Path, content: &[u8]) -> Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // The mode only applies to new files, so tighten existing ones too
+        if path.exists() {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        }
+    }
+
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    std::io::Write::write_all(&mut file, content)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Path of `<name>.json` in the data directory, creating the directory if needed
+fn path(name: &str) -> Result<PathBuf> {
+    file_path(&format!("{name}.json"))
+}
+
+fn file_path(file_name: &str) -> Result<PathBuf> {
+    let dir = data_dir()?;
+    std::fs::create_dir_all(&dir)
+        .with_context(|| format!("Failed to create data directory {}", dir.display()))?;
+    Ok(dir.join(file_name))
+}
@@ -0,0 +1,633 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use colored::*;
+use futures::stream::{self, Stream, TryStreamExt};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const API_BASE: &str = "https://www.strava.com/api/v3";
+
+// Max allowed by Strava
+const PER_PAGE: usize = 200;
+
+/// Length of Strava's short-term rate limit window, which resets at the
+/// quarter hour
+const RATE_LIMIT_WINDOW_SECS: i64 = 15 * 60;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Activity {
+    pub id: i64,
+    pub name: String,
+    pub distance: f64,
+    pub moving_time: i32,
+    pub elapsed_time: i32,
+    pub total_elevation_gain: f64,
+    #[serde(rename = "type")]
+    pub activity_type: String,
+    pub start_date: String,
+    pub gear_id: Option<String>,
+    pub average_watts: Option<f64>,
+    pub weighted_average_watts: Option<f64>,
+    pub kilojoules: Option<f64>,
+    pub average_heartrate: Option<f64>,
+    /// Whether the activity was recorded on an indoor trainer
+    #[serde(default)]
+    pub trainer: bool,
+    /// Marked "Only You" on Strava
+    #[serde(default)]
+    pub private: bool,
+    /// Who can see the activity: `everyone`, `followers_only` or `only_me`
+    #[serde(default)]
+    pub visibility: Option<String>,
+}
+
+impl Activity {
+    /// Whether everyone can see the activity, which is all Strava's athlete
+    /// stats count. Activities cached before visibility was recorded count as public.
+    pub fn is_public(&self) -> bool {
+        !self.private && self.visibility.as_deref().is_none_or(|v| v == "everyone")
+    }
+
+    /// Calendar day (UTC) the activity started on
+    pub fn date(&self) -> Option<NaiveDate> {
+        parse_start_date(&self.start_date)
+    }
+
+    /// Unix timestamp the activity started at
+    pub fn start_timestamp(&self) -> Option<i64> {
+        chrono::DateTime::parse_from_rfc3339(&self.start_date)
+            .ok()
+            .map(|start| start.timestamp())
+    }
+}
+
+/// Full activity details as returned by `/activities/{id}`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DetailedActivity {
+    pub id: i64,
+    pub name: String,
+    pub distance: f64,
+    pub moving_time: i32,
+    pub elapsed_time: i32,
+    pub total_elevation_gain: f64,
+    #[serde(rename = "type")]
+    pub activity_type: String,
+    pub start_date: String,
+    pub description: Option<String>,
+    pub gear_id: Option<String>,
+    pub average_speed: Option<f64>,
+    pub max_speed: Option<f64>,
+    pub average_watts: Option<f64>,
+    pub weighted_average_watts: Option<f64>,
+    pub max_watts: Option<f64>,
+    pub device_watts: Option<bool>,
+    pub kilojoules: Option<f64>,
+    pub calories: Option<f64>,
+    pub average_heartrate: Option<f64>,
+    pub max_heartrate: Option<f64>,
+    pub average_cadence: Option<f64>,
+    pub map: Option<PolylineMap>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PolylineMap {
+    pub polyline: Option<String>,
+    pub summary_polyline: Option<String>,
+}
+
+/// A bike or pair of shoes as returned by `/gear/{id}`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Gear {
+    pub id: String,
+    pub name: Option<String>,
+    pub brand_name: Option<String>,
+    pub model_name: Option<String>,
+    #[serde(default)]
+    pub retired: bool,
+    /// Total distance Strava has recorded for this gear, in meters
+    #[serde(default)]
+    pub distance: f64,
+}
+
+/// A bike or pair of shoes as listed on the athlete
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SummaryGear {
+    pub id: String,
+    pub name: Option<String>,
+    #[serde(default)]
+    pub primary: bool,
+    /// Total distance Strava has recorded for this gear, in meters
+    #[serde(default)]
+    pub distance: f64,
+}
+
+/// The authenticated athlete as returned by `/athlete`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Athlete {
+    pub id: i64,
+    pub firstname: Option<String>,
+    pub lastname: Option<String>,
+    #[serde(default)]
+    pub bikes: Vec<SummaryGear>,
+    #[serde(default)]
+    pub shoes: Vec<SummaryGear>,
+}
+
+/// Totals of one sport over one period, as returned by `/athletes/{id}/stats`
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ActivityTotal {
+    pub count: i64,
+    /// Meters
+    pub distance: f64,
+    /// Seconds
+    pub moving_time: i64,
+    /// Meters
+    pub elevation_gain: f64,
+}
+
+/// Recent (last four weeks), year-to-date and all-time totals of an athlete's
+/// public activities
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ActivityStats {
+    /// Meters
+    pub biggest_ride_distance: Option<f64>,
+    /// Meters
+    pub biggest_climb_elevation_gain: Option<f64>,
+    pub recent_ride_totals: ActivityTotal,
+    pub recent_run_totals: ActivityTotal,
+    pub recent_swim_totals: ActivityTotal,
+    pub ytd_ride_totals: ActivityTotal,
+    pub ytd_run_totals: ActivityTotal,
+    pub ytd_swim_totals: ActivityTotal,
+    pub all_ride_totals: ActivityTotal,
+    pub all_run_totals: ActivityTotal,
+    pub all_swim_totals: ActivityTotal,
+}
+
+/// Stream types requested from `/activities/{id}/streams`, in export column order
+pub const STREAM_KEYS: &[&str] = &[
+    "time",
+    "distance",
+    "latlng",
+    "altitude",
+    "velocity_smooth",
+    "heartrate",
+    "cadence",
+    "watts",
+    "temp",
+    "moving",
+    "grade_smooth",
+];
+
+/// A single data stream, e.g. heart rate samples over the whole activity
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ActivityStream {
+    pub data: Vec<serde_json::Value>,
+    pub series_type: Option<String>,
+    pub original_size: Option<usize>,
+    pub resolution: Option<String>,
+}
+
+/// Streams keyed by their type (`time`, `heartrate`, `latlng`, ...)
+pub type StreamSet = BTreeMap<String, ActivityStream>;
+
+/// Which activities to page through on `/athlete/activities`
+#[derive(Debug, Clone, Default)]
+pub struct ActivityQuery {
+    /// Only activities starting after this Unix timestamp
+    pub after: Option<i64>,
+    /// Only activities starting before this Unix timestamp
+    pub before: Option<i64>,
+    /// Maximum number of pages to request concurrently
+    pub concurrency: usize,
+    /// Print a line for every fetched page
+    pub verbose: bool,
+}
+
+/// Stream every activity matching `query`, in the order Strava returns them.
+///
+/// Pages are requested lazily, so dropping the stream early stops any further
+/// requests. Once the rate limit budget is known, several pages are requested
+/// at once (see [`page_batch_size`]); once the 15-minute budget is used up,
+/// paging waits for the window to reset rather than sending requests bound to
+/// be rejected. A used-up daily budget ends the stream with an error.
+pub fn activities(
+    client: reqwest::Client,
+    token: String,
+    query: ActivityQuery,
+) -> impl Stream<Item = Result<Activity>> {
+    let pager = Pager {
+        client,
+        token,
+        query,
+        next_page: 1,
+        remaining: None,
+        done: false,
+    };
+
+    stream::try_unfold(pager, |mut pager| async move {
+        let batch = pager.next_batch().await?;
+        Ok::<_, anyhow::Error>(batch.map(|activities| (activities, pager)))
+    })
+    .map_ok(|activities| stream::iter(activities.into_iter().map(Ok)))
+    .try_flatten()
+}
+
+struct Pager {
+    client: reqwest::Client,
+    token: String,
+    query: ActivityQuery,
+    next_page: usize,
+    remaining: Option<RateBudget>,
+    done: bool,
+}
+
+impl Pager {
+    /// Speculatively request the next batch of pages, returning their
+    /// activities in page order, or `None` once the last page has been seen
+    async fn next_batch(&mut self) -> Result<Option<Vec<Activity>>> {
+        if self.done {
+            return Ok(None);
+        }
+
+        let batch = match page_batch_size(self.query.concurrency, self.remaining)? {
+            Some(batch) => batch,
+            None => {
+                let wait = until_window_reset(chrono::Utc::now().timestamp());
+                eprintln!(
+                    "{} {}s {}",
+                    "⏳ Strava's rate limit is used up, waiting".yellow(),
+                    wait.as_secs().to_string().bright_white(),
+                    "for the 15-minute window to reset".yellow()
+                );
+                tokio::time::sleep(wait).await;
+                // Probe with a single page to learn the new budget
+                1
+            }
+        };
+        let mut requests = tokio::task::JoinSet::new();
+        for page in self.next_page..self.next_page + batch {
+            let client = self.client.clone();
+            let token = self.token.clone();
+            let query = self.query.clone();
+            requests.spawn(async move {
+                let result = fetch_activity_page(&client, &token, &query, page).await;
+                (page, result)
+            });
+        }
+
+        let mut pages = Vec::with_capacity(batch);
+        while let Some(joined) = requests.join_next().await {
+            pages.push(joined?);
+        }
+        pages.sort_by_key(|(page, _)| *page);
+        self.next_page += batch;
+
+        let mut activities = Vec::new();
+        let mut budgets = Vec::with_capacity(batch);
+        for (page, result) in pages {
+            let (page_activities, page_remaining) = result?;
+            budgets.push(page_remaining);
+
+            if self.query.verbose && !page_activities.is_empty() {
+                println!(
+                    "{} {} activities from page {}",
+                    "📄 Fetched".cyan(),
+                    page_activities.len().to_string().bright_white().bold(),
+                    page.to_string().bright_white().bold()
+                );
+            }
+
+            // If we got fewer activities than requested, we've reached the end
+            // and any later pages in this batch are empty
+            let last = page_activities.len() < PER_PAGE;
+            activities.extend(page_activities);
+            if last {
+                self.done = true;
+                break;
+            }
+        }
+        self.remaining = latest_remaining(budgets).or(self.remaining);
+
+        Ok(Some(activities))
+    }
+}
+
+/// Unix timestamp of midnight UTC at the start of `date`
+pub fn day_timestamp(date: NaiveDate) -> i64 {
+    date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp()
+}
+
+/// Parse the day out of an ISO 8601 timestamp such as `2024-01-15T07:30:00Z`
+fn parse_start_date(start_date: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(start_date.get(..10)?, "%Y-%m-%d").ok()
+}
+
+/// Fetch the detailed representation of a single activity
+pub async fn get_activity(
+    client: &reqwest::Client,
+    token: &str,
+    id: i64,
+) -> Result<DetailedActivity> {
+    get_json(client, token, &format!("activities/{id}"), &[]).await
+}
+
+/// Fetch a single activity in the summary representation returned by the
+/// activity list, e.g. to refresh one cached activity
+pub async fn get_activity_summary(
+    client: &reqwest::Client,
+    token: &str,
+    id: i64,
+) -> Result<Activity> {
+    get_json(client, token, &format!("activities/{id}"), &[]).await
+}
+
+/// Whether the activity still exists on Strava, i.e. isn't answered with 404
+pub async fn activity_exists(client: &reqwest::Client, token: &str, id: i64) -> Result<bool> {
+    let response = client
+        .get(format!("{API_BASE}/activities/{id}"))
+        .header("Authorization", format!("Bearer {token}"))
+        .send()
+        .await?;
+
+    match response.status() {
+        reqwest::StatusCode::NOT_FOUND => Ok(false),
+        status if status.is_success() => Ok(true),
+        _ => {
+            let error_text = response.text().await?;
+            Err(anyhow::anyhow!("Strava API error: {error_text}"))
+        }
+    }
+}
+
+/// Fetch all available data streams for a single activity
+pub async fn get_activity_streams(
+    client: &reqwest::Client,
+    token: &str,
+    id: i64,
+) -> Result<StreamSet> {
+    let query = [
+        ("keys", STREAM_KEYS.join(",")),
+        ("key_by_type", "true".to_string()),
+    ];
+    get_json(client, token, &format!("activities/{id}/streams"), &query).await
+}
+
+/// Fetch the authenticated athlete, including their bikes and shoes
+pub async fn get_athlete(client: &reqwest::Client, token: &str) -> Result<Athlete> {
+    get_json(client, token, "athlete", &[]).await
+}
+
+/// Fetch the activity stats of the authenticated athlete, whose ID must be `athlete_id`
+pub async fn get_athlete_stats(
+    client: &reqwest::Client,
+    token: &str,
+    athlete_id: i64,
+) -> Result<ActivityStats> {
+    get_json(client, token, &format!("athletes/{athlete_id}/stats"), &[]).await
+}
+
+/// Fetch a single piece of gear
+pub async fn get_gear(client: &reqwest::Client, token: &str, id: &str) -> Result<Gear> {
+    get_json(client, token, &format!("gear/{id}"), &[]).await
+}
+
+/// Assign an activity to a piece of gear, returning the updated activity.
+/// Requires the `activity:write` scope.
+pub async fn update_activity_gear(
+    client: &reqwest::Client,
+    token: &str,
+    id: i64,
+    gear_id: &str,
+) -> Result<Activity> {
+    let response = client
+        .put(format!("{API_BASE}/activities/{id}"))
+        .header("Authorization", format!("Bearer {token}"))
+        .json(&serde_json::json!({ "gear_id": gear_id }))
+        .send()
+        .await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response.text().await?;
+        if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+            return Err(anyhow::anyhow!(
+                "Strava refused the update ({error_text}). Run 'chain-life auth' again to grant the activity:write scope"
+            ));
+        }
+        return Err(anyhow::anyhow!("Strava API error: {error_text}"));
+    }
+
+    Ok(response.json().await?)
+}
+
+/// Issue an authenticated GET against the Strava API and decode the JSON body
+async fn get_json<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    token: &str,
+    path: &str,
+    query: &[(&str, String)],
+) -> Result<T> {
+    let response = client
+        .get(format!("{API_BASE}/{path}"))
+        .header("Authorization", format!("Bearer {token}"))
+        .query(query)
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        let error_text = response.text().await?;
+        return Err(anyhow::anyhow!("Strava API error: {error_text}"));
+    }
+
+    Ok(response.json().await?)
+}
+
+/// Fetch a single page of activities, returning it along with the remaining
+/// request budget reported by Strava's rate limit headers (if any)
+async fn fetch_activity_page(
+    client: &reqwest::Client,
+    token: &str,
+    query: &ActivityQuery,
+    page: usize,
+) -> Result<(Vec<Activity>, Option<RateBudget>)> {
+    let mut params = vec![
+        ("page", page.to_string()),
+        ("per_page", PER_PAGE.to_string()),
+    ];
+    if let Some(after) = query.after {
+        params.push(("after", after.to_string()));
+    }
+    if let Some(before) = query.before {
+        params.push(("before", before.to_string()));
+    }
+
+    let response = client
+        .get(format!("{API_BASE}/athlete/activities"))
+        .header("Authorization", format!("Bearer {token}"))
+        .query(&params)
+        .send()
+        .await?;
+
+    let remaining = remaining_requests(response.headers());
+
+    if !response.status().is_success() {
+        let error_text = response.text().await?;
+        return Err(anyhow::anyhow!("Strava API error: {error_text}"));
+    }
+
+    let activities: Vec<Activity> = response.json().await?;
+    Ok((activities, remaining))
+}
+
+/// Requests left in each of Strava's rate limit windows
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct RateBudget {
+    /// Left in the current 15-minute window
+    short_term: u32,
+    /// Left until midnight UTC
+    daily: u32,
+}
+
+/// Compute how many requests are left before hitting Strava's rate limits.
+///
+/// Strava reports `X-RateLimit-Limit` and `X-RateLimit-Usage` as comma-separated
+/// pairs for the 15-minute and daily windows.
+fn remaining_requests(headers: &reqwest::header::HeaderMap) -> Option<RateBudget> {
+    let parse = |name: &str| -> Option<Vec<u32>> {
+        headers
+            .get(name)?
+            .to_str()
+            .ok()?
+            .split(',')
+            .map(|v| v.trim().parse().ok())
+            .collect()
+    };
+
+    match (
+        parse("X-RateLimit-Limit")?.as_slice(),
+        parse("X-RateLimit-Usage")?.as_slice(),
+    ) {
+        ([short_limit, daily_limit, ..], [short_used, daily_used, ..]) => Some(RateBudget {
+            short_term: short_limit.saturating_sub(*short_used),
+            daily: daily_limit.saturating_sub(*daily_used),
+        }),
+        _ => None,
+    }
+}
+
+/// Remaining budget after a batch: the lowest any of its responses reported
+/// for each window. Earlier batches don't count, since a new rate limit window
+/// may have started since then.
+fn latest_remaining(batch: impl IntoIterator<Item = Option<RateBudget>>) -> Option<RateBudget> {
+    batch.into_iter().flatten().reduce(|a, b| RateBudget {
+        short_term: a.short_term.min(b.short_term),
+        daily: a.daily.min(b.daily),
+    })
+}
+
+/// Number of pages to request at once, bounded by the configured concurrency
+/// and the remaining rate limit budget. Until Strava has told us the budget,
+/// only a single page is requested; `None` means the 15-minute window is used
+/// up. Waiting out a used-up daily limit would take hours, so that's an error.
+fn page_batch_size(concurrency: usize, remaining: Option<RateBudget>) -> Result<Option<usize>> {
+    match remaining {
+        Some(budget) if budget.daily == 0 => Err(anyhow::anyhow!(
+            "Strava's daily rate limit is used up; try again after midnight UTC"
+        )),
+        Some(budget) if budget.short_term == 0 => Ok(None),
+        Some(budget) => Ok(Some(
+            concurrency
+                .min(budget.short_term.min(budget.daily) as usize)
+                .max(1),
+        )),
+        None => Ok(Some(1)),
+    }
+}
+
+/// Time from Unix time `now` until the short-term rate limit window resets
+fn until_window_reset(now: i64) -> std::time::Duration {
+    let secs = RATE_LIMIT_WINDOW_SECS - now.rem_euclid(RATE_LIMIT_WINDOW_SECS);
+    std::time::Duration::from_secs(secs as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_start_date() {
+        assert_eq!(
+            parse_start_date("2024-01-15T07:30:00Z"),
+            NaiveDate::from_ymd_opt(2024, 1, 15)
+        );
+        assert_eq!(parse_start_date("garbage"), None);
+    }
+
+    #[test]
+    fn test_deserialize_streams_keyed_by_type() {
+        let json = r#"{
+            "time": {"data": [0, 1, 2], "series_type": "distance", "original_size": 3, "resolution": "high"},
+            "latlng": {"data": [[52.1, 4.3], [52.2, 4.4], [52.3, 4.5]], "series_type": "distance", "original_size": 3, "resolution": "high"}
+        }"#;
+        let streams: StreamSet = serde_json::from_str(json).unwrap();
+        assert_eq!(streams["time"].data.len(), 3);
+        assert_eq!(streams["latlng"].data[1], serde_json::json!([52.2, 4.4]));
+    }
+
+    fn budget(short_term: u32, daily: u32) -> Option<RateBudget> {
+        Some(RateBudget { short_term, daily })
+    }
+
+    #[test]
+    fn test_remaining_requests_keeps_windows_apart() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("X-RateLimit-Limit", "200,2000".parse().unwrap());
+        headers.insert("X-RateLimit-Usage", "190,500".parse().unwrap());
+        assert_eq!(remaining_requests(&headers), budget(10, 1500));
+    }
+
+    #[test]
+    fn test_remaining_requests_missing_headers() {
+        let headers = reqwest::header::HeaderMap::new();
+        assert_eq!(remaining_requests(&headers), None);
+    }
+
+    #[test]
+    fn test_latest_remaining() {
+        assert_eq!(
+            latest_remaining([budget(5, 900), None, budget(3, 1000)]),
+            budget(3, 900)
+        );
+        assert_eq!(latest_remaining([None, None]), None);
+        // A reset window reports more budget than the previous batch left
+        assert_eq!(latest_remaining([budget(195, 900)]), budget(195, 900));
+    }
+
+    #[test]
+    fn test_page_batch_size() {
+        assert_eq!(page_batch_size(4, None).unwrap(), Some(1));
+        assert_eq!(page_batch_size(4, budget(100, 1000)).unwrap(), Some(4));
+        assert_eq!(page_batch_size(4, budget(2, 1000)).unwrap(), Some(2));
+        assert_eq!(page_batch_size(4, budget(100, 3)).unwrap(), Some(3));
+        assert_eq!(page_batch_size(4, budget(0, 1000)).unwrap(), None);
+        assert_eq!(page_batch_size(0, budget(100, 1000)).unwrap(), Some(1));
+    }
+
+    #[test]
+    fn test_page_batch_size_daily_limit_used_up() {
+        // Waiting for the 15-minute window wouldn't help
+        let err = page_batch_size(4, budget(100, 0)).unwrap_err();
+        assert!(err.to_string().contains("daily rate limit"));
+        assert!(page_batch_size(4, budget(0, 0)).is_err());
+    }
+
+    #[test]
+    fn test_until_window_reset() {
+        let quarter_hour = 1_700_000_100; // 22:15:00 UTC
+        assert_eq!(until_window_reset(quarter_hour).as_secs(), 900);
+        assert_eq!(until_window_reset(quarter_hour + 1).as_secs(), 899);
+        assert_eq!(until_window_reset(quarter_hour + 840).as_secs(), 60);
+    }
+}
@@ -0,0 +1,71 @@
+//! Fixtures shared by the unit tests
+
+use crate::component::Component;
+use crate::strava::Activity;
+use chrono::NaiveDate;
+
+/// Parse a `YYYY-MM-DD` day
+pub fn day(s: &str) -> NaiveDate {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+}
+
+/// An hour-long ride of `km` starting at 08:00 UTC on `date` (`YYYY-MM-DD`),
+/// without gear. Adjust it with [`of_type`], [`on_gear`] or struct update syntax.
+pub fn activity(id: i64, date: &str, km: f64) -> Activity {
+    Activity {
+        id,
+        name: format!("Activity {id}"),
+        distance: km * 1000.0,
+        moving_time: 3600,
+        elapsed_time: 3600,
+        total_elevation_gain: 0.0,
+        activity_type: "Ride".to_string(),
+        start_date: format!("{date}T08:00:00Z"),
+        gear_id: None,
+        average_watts: None,
+        weighted_average_watts: None,
+        kilojoules: None,
+        average_heartrate: None,
+        trainer: false,
+        private: false,
+        visibility: None,
+    }
+}
+
+/// A chain called "KMC X11" fitted on `installed` (`YYYY-MM-DD`), worn by
+/// cycling on any bike
+pub fn chain(installed: &str, rated_km: f64) -> Component {
+    Component {
+        name: "KMC X11".to_string(),
+        kind: "chain".to_string(),
+        installed: day(installed),
+        rated_km,
+        gear_id: None,
+        activity_types: "cycling".to_string(),
+        price: None,
+    }
+}
+
+/// `activity` with its type changed, e.g. to `Run`
+pub fn of_type(activity: Activity, activity_type: &str) -> Activity {
+    Activity {
+        activity_type: activity_type.to_string(),
+        ..activity
+    }
+}
+
+/// `activity` recorded on the gear `gear_id`
+pub fn on_gear(activity: Activity, gear_id: &str) -> Activity {
+    Activity {
+        gear_id: Some(gear_id.to_string()),
+        ..activity
+    }
+}
+
+/// `component` fitted to the bike `gear_id`
+pub fn fitted_to(component: Component, gear_id: &str) -> Component {
+    Component {
+        gear_id: Some(gear_id.to_string()),
+        ..component
+    }
+}
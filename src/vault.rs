@@ -0,0 +1,223 @@
+use crate::store;
+use age::secrecy::SecretString;
+use anyhow::{Context, Result};
+use std::io::IsTerminal;
+use std::sync::Mutex;
+
+/// Environment variable with the passphrase of the encrypted credentials file
+pub const PASSPHRASE_ENV: &str = "CHAIN_LIFE_PASSPHRASE";
+
+/// Environment variable with the passphrase of a bundle written by `auth export`
+pub const BUNDLE_PASSPHRASE_ENV: &str = "CHAIN_LIFE_BUNDLE_PASSPHRASE";
+
+/// Environment variable that, set to `off`, skips the keyring and always uses
+/// the encrypted file
+const KEYRING_ENV: &str = "CHAIN_LIFE_KEYRING";
+
+/// Keyring service name; entries are per data directory
+const SERVICE: &str = "chain-life";
+
+const ENCRYPTED_FILE: &str = "credentials.age";
+
+/// Where earlier versions saved credentials, unencrypted
+const PLAINTEXT_FILE: &str = "credentials.json";
+
+/// Passphrase entered once per run, so refreshing a token doesn't ask again
+static PASSPHRASE: Mutex<Option<String>> = Mutex::new(None);
+
+/// Where a secret is kept
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Location {
+    /// The OS keyring: the Secret Service on Linux, Keychain on macOS,
+    /// Credential Manager on Windows
+    Keyring,
+    /// `credentials.age` in the data directory, encrypted with a passphrase
+    EncryptedFile,
+    /// `credentials.json` in the data directory, as saved by earlier versions
+    PlaintextFile,
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Location::Keyring => write!(f, "the system keyring"),
+            Location::EncryptedFile => write!(f, "{ENCRYPTED_FILE} (passphrase-encrypted)"),
+            Location::PlaintextFile => write!(f, "{PLAINTEXT_FILE} (plaintext)"),
+        }
+    }
+}
+
+/// Keyring entry for the current data directory, unless the keyring is turned off
+fn keyring_entry() -> Result<Option<keyring::Entry>> {
+    if std::env::var(KEYRING_ENV).is_ok_and(|value| value == "off") {
+        return Ok(None);
+    }
+    let user = store::data_dir()?.display().to_string();
+    // Without a usable keyring backend the encrypted file is used instead
+    Ok(keyring::Entry::new(SERVICE, &user).ok())
+}
+
+/// A secret from `$<env>`, or prompted for without echoing it (twice if
+/// `confirm`) when running in a terminal
+pub fn read_secret(env: &str, name: &str, confirm: bool) -> Result<String> {
+    let secret = match std::env::var(env) {
+        Ok(secret) => secret,
+        Err(_) if std::io::stdin().is_terminal() || std::io::stderr().is_terminal() => {
+            let secret = rpassword::prompt_password(format!("{name}: "))?;
+            if confirm && rpassword::prompt_password(format!("Repeat the {name}: "))? != secret {
+                return Err(anyhow::anyhow!("The {name}s don't match"));
+            }
+            secret
+        }
+        Err(_) => {
+            return Err(anyhow::anyhow!(
+                "No {name}: set {env} or run 'chain-life' in a terminal to be prompted for it"
+            ))
+        }
+    };
+    if secret.trim().is_empty() {
+        return Err(anyhow::anyhow!("The {name} can't be empty"));
+    }
+    Ok(secret)
+}
+
+/// Passphrase for the encrypted file: `$CHAIN_LIFE_PASSPHRASE`, or prompted
+/// for (twice if it's a new one)
+fn passphrase(new: bool) -> Result<String> {
+    let mut cached = PASSPHRASE.lock().unwrap();
+    if let Some(passphrase) = cached.as_ref() {
+        return Ok(passphrase.clone());
+    }
+
+    let passphrase = read_secret(PASSPHRASE_ENV, "credentials file passphrase", new)
+        .context("No keyring available, so credentials are kept in an encrypted file")?;
+    *cached = Some(passphrase.clone());
+    Ok(passphrase)
+}
+
+fn decrypt(ciphertext: &[u8], passphrase: &str) -> Result<String> {
+    let identity = age::scrypt::Identity::new(SecretString::from(passphrase.to_string()));
+    let plaintext = age::decrypt(&identity, ciphertext)?;
+    String::from_utf8(plaintext).context("Decrypted credentials aren't valid UTF-8")
+}
+
+/// Encrypt `secret` into a text bundle to move to another machine, with a
+/// passphrase from `$CHAIN_LIFE_BUNDLE_PASSPHRASE` or prompted for
+pub fn seal(secret: &str) -> Result<String> {
+    let passphrase = read_secret(BUNDLE_PASSPHRASE_ENV, "bundle passphrase", true)?;
+    let recipient = age::scrypt::Recipient::new(SecretString::from(passphrase));
+    age::encrypt_and_armor(&recipient, secret.as_bytes())
+        .map_err(|error| anyhow::anyhow!("Failed to encrypt the bundle: {error}"))
+}
+
+/// Decrypt a bundle written by [`seal`]
+pub fn unseal(bundle: &[u8]) -> Result<String> {
+    let passphrase = read_secret(BUNDLE_PASSPHRASE_ENV, "bundle passphrase", false)?;
+    decrypt(bundle, &passphrase).context("Failed to decrypt the bundle")
+}
+
+fn encrypt(plaintext: &str, passphrase: &str) -> Result<Vec<u8>> {
+    let recipient = age::scrypt::Recipient::new(SecretString::from(passphrase.to_string()));
+    age::encrypt(&recipient, plaintext.as_bytes())
+        .map_err(|error| anyhow::anyhow!("Failed to encrypt credentials: {error}"))
+}
+
+/// Load the secret from the keyring, the encrypted file or, failing those, the
+/// plaintext file of earlier versions
+pub fn load() -> Result<Option<(String, Location)>> {
+    if let Some(entry) = keyring_entry()? {
+        // With no entry, or no usable keyring, fall back to the files
+        if let Ok(secret) = entry.get_password() {
+            return Ok(Some((secret, Location::Keyring)));
+        }
+    }
+
+    let dir = store::data_dir()?;
+    let encrypted = dir.join(ENCRYPTED_FILE);
+    if encrypted.exists() {
+        let ciphertext = std::fs::read(&encrypted)
+            .with_context(|| format!("Failed to read {}", encrypted.display()))?;
+        let secret = decrypt(&ciphertext, &passphrase(false)?)
+            .with_context(|| format!("Failed to decrypt {ENCRYPTED_FILE}"));
+        if secret.is_err() {
+            // Let a mistyped passphrase be entered again on the next attempt
+            PASSPHRASE.lock().unwrap().take();
+        }
+        return Ok(Some((secret?, Location::EncryptedFile)));
+    }
+
+    let plaintext = dir.join(PLAINTEXT_FILE);
+    if plaintext.exists() {
+        let secret = std::fs::read_to_string(&plaintext)
+            .with_context(|| format!("Failed to read {}", plaintext.display()))?;
+        return Ok(Some((secret, Location::PlaintextFile)));
+    }
+    Ok(None)
+}
+
+/// Save the secret in the keyring, or the encrypted file if there's no usable
+/// keyring, removing any copy left elsewhere
+pub fn save(secret: &str) -> Result<Location> {
+    let dir = store::data_dir()?;
+    let saved_in_keyring = match keyring_entry()? {
+        Some(entry) => entry.set_password(secret).is_ok(),
+        None => false,
+    };
+
+    let location = if saved_in_keyring {
+        remove_file(&dir.join(ENCRYPTED_FILE))?;
+        Location::Keyring
+    } else {
+        let passphrase = passphrase(!dir.join(ENCRYPTED_FILE).exists())?;
+        store::write_private(ENCRYPTED_FILE, &encrypt(secret, &passphrase)?)?;
+        Location::EncryptedFile
+    };
+    remove_file(&dir.join(PLAINTEXT_FILE))?;
+    Ok(location)
+}
+
+/// Remove the secret from the keyring and both files
+pub fn delete() -> Result<()> {
+    if let Some(entry) = keyring_entry()? {
+        match entry.delete_credential() {
+            // Nothing saved there, or no keyring to save it in
+            Ok(()) | Err(keyring::Error::NoEntry) | Err(keyring::Error::PlatformFailure(_)) => {}
+            Err(error) => {
+                return Err(error).context("Failed to remove credentials from the keyring")
+            }
+        }
+    }
+
+    let dir = store::data_dir()?;
+    remove_file(&dir.join(ENCRYPTED_FILE))?;
+    remove_file(&dir.join(PLAINTEXT_FILE))?;
+    PASSPHRASE.lock().unwrap().take();
+    Ok(())
+}
+
+fn remove_file(path: &std::path::Path) -> Result<()> {
+    if path.exists() {
+        std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove {}", path.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decrypt() {
+        let mut recipient = age::scrypt::Recipient::new(SecretString::from("hunter2".to_string()));
+        // Keep the test fast; the real files use age's calibrated work factor
+        recipient.set_work_factor(10);
+        let ciphertext = age::encrypt(&recipient, br#"{"access_token": "abc"}"#).unwrap();
+
+        assert_eq!(
+            decrypt(&ciphertext, "hunter2").unwrap(),
+            r#"{"access_token": "abc"}"#
+        );
+        assert!(decrypt(&ciphertext, "wrong").is_err());
+    }
+}
@@ -0,0 +1,423 @@
+use crate::cache::ActivityCache;
+use crate::credentials::{self, Credentials, Profile};
+use crate::store;
+use crate::strava::{self, API_BASE};
+use anyhow::{Context, Result};
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Json, Router};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Event POSTed by Strava for each change to a subscribed athlete's data
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Event {
+    /// `activity` or `athlete`
+    pub object_type: String,
+    pub object_id: i64,
+    /// `create`, `update` or `delete`
+    pub aspect_type: String,
+    pub owner_id: i64,
+    pub subscription_id: i64,
+    pub event_time: i64,
+    /// Changed fields, e.g. `{"title": "Evening Ride"}` or `{"authorized": "false"}`
+    #[serde(default)]
+    pub updates: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Data file with the application's push subscription, saved by `subscribe`
+const SUBSCRIPTION_FILE: &str = "subscription";
+
+/// What an event means for the local cache
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    /// Fetch the activity and insert or replace it
+    Upsert(i64),
+    /// Drop the activity
+    Remove(i64),
+    /// The athlete revoked access
+    Deauthorized,
+    Ignore,
+}
+
+impl Event {
+    /// Whether the event was sent for the application's subscription; unknown
+    /// subscriptions never match
+    pub fn is_for(&self, subscription_id: Option<i64>) -> bool {
+        subscription_id == Some(self.subscription_id)
+    }
+
+    /// Decide how to apply this event, ignoring other athletes' events when
+    /// `athlete_id` is known
+    pub fn action(&self, athlete_id: Option<i64>) -> Action {
+        if athlete_id.is_some_and(|id| id != self.owner_id) {
+            return Action::Ignore;
+        }
+
+        match (self.object_type.as_str(), self.aspect_type.as_str()) {
+            ("activity", "create" | "update") => Action::Upsert(self.object_id),
+            ("activity", "delete") => Action::Remove(self.object_id),
+            ("athlete", "update")
+                if self.updates.get("authorized").and_then(|v| v.as_str()) == Some("false") =>
+            {
+                Action::Deauthorized
+            }
+            _ => Action::Ignore,
+        }
+    }
+}
+
+/// Query parameters of the subscription validation request
+#[derive(Deserialize, Debug)]
+struct Challenge {
+    #[serde(rename = "hub.mode")]
+    mode: String,
+    #[serde(rename = "hub.verify_token")]
+    verify_token: String,
+    #[serde(rename = "hub.challenge")]
+    challenge: String,
+}
+
+#[derive(Serialize, Debug)]
+struct ChallengeResponse {
+    #[serde(rename = "hub.challenge")]
+    challenge: String,
+}
+
+#[derive(Clone)]
+struct WebhookState {
+    verify_token: Arc<String>,
+    athlete_id: Option<i64>,
+    /// The application's subscription, looked up once at startup
+    subscription_id: Option<i64>,
+    /// Serializes cache updates from concurrently delivered events
+    cache_lock: Arc<Mutex<()>>,
+}
+
+/// ID of the application's subscription: the one saved by `subscribe`, or
+/// else the one Strava lists for the application
+async fn subscription_id() -> Result<Option<i64>> {
+    if let Some(subscription) = store::load::<Option<Subscription>>(SUBSCRIPTION_FILE)? {
+        return Ok(Some(subscription.id));
+    }
+    Ok(list_subscriptions()
+        .await?
+        .first()
+        .map(|subscription| subscription.id))
+}
+
+async fn validate(
+    State(state): State<WebhookState>,
+    Query(challenge): Query<Challenge>,
+) -> Result<Json<ChallengeResponse>, StatusCode> {
+    if challenge.mode != "subscribe" || challenge.verify_token != *state.verify_token {
+        eprintln!(
+            "{}",
+            "⚠️  Rejected subscription validation with a wrong verify token".yellow()
+        );
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    println!("{}", "✅ Subscription validated".bright_green().bold());
+    Ok(Json(ChallengeResponse {
+        challenge: challenge.challenge,
+    }))
+}
+
+async fn receive(State(state): State<WebhookState>, Json(event): Json<Event>) -> StatusCode {
+    // Anyone can POST to the callback, so only accept events for our subscription
+    if !event.is_for(state.subscription_id) {
+        eprintln!(
+            "{} {}",
+            "⚠️  Rejected an event for unknown subscription".yellow(),
+            event.subscription_id
+        );
+        return StatusCode::FORBIDDEN;
+    }
+
+    // Strava expects a response within two seconds, so apply the event in the background
+    tokio::spawn(async move {
+        let _guard = state.cache_lock.lock().await;
+        if let Err(error) = apply(&event, state.athlete_id).await {
+            eprintln!(
+                "{} {} {}: {error:#}",
+                "⚠️  Failed to apply".yellow().bold(),
+                event.aspect_type,
+                event.object_id
+            );
+        }
+    });
+    StatusCode::OK
+}
+
+async fn apply(event: &Event, athlete_id: Option<i64>) -> Result<()> {
+    match event.action(athlete_id) {
+        Action::Upsert(id) => {
+            let token =
+                credentials::access_token(None, &[credentials::READ_ALL_ACTIVITIES]).await?;
+            let activity =
+                strava::get_activity_summary(&reqwest::Client::new(), &token, id).await?;
+            let mut cache = ActivityCache::load()?;
+            println!(
+                "{} {} ({})",
+                "🔄 Updated activity".cyan(),
+                activity.name.bright_white(),
+                id
+            );
+            cache.merge(vec![activity]);
+            cache.save()?;
+        }
+        Action::Remove(id) => {
+            // A forged delete event must not drop an activity Strava still has
+            let token =
+                credentials::access_token(None, &[credentials::READ_ALL_ACTIVITIES]).await?;
+            if strava::activity_exists(&reqwest::Client::new(), &token, id).await? {
+                eprintln!(
+                    "{} {}",
+                    "⚠️  Ignored a delete event for an activity that still exists:".yellow(),
+                    id
+                );
+                return Ok(());
+            }
+            let mut cache = ActivityCache::load()?;
+            if cache.remove(id) {
+                cache.save()?;
+                println!("{} {}", "🗑️  Removed activity".cyan(), id);
+            }
+        }
+        Action::Deauthorized => {
+            eprintln!(
+                "{}",
+                "⚠️  The athlete revoked access. Run 'chain-life auth' again to resume syncing."
+                    .yellow()
+                    .bold()
+            );
+        }
+        Action::Ignore => {}
+    }
+    Ok(())
+}
+
+fn router(state: WebhookState) -> Router {
+    Router::new()
+        .route("/webhook", get(validate).post(receive))
+        .with_state(state)
+}
+
+pub async fn handle_serve(addr: String, verify_token: String) -> Result<()> {
+    let athlete_id = Profile::load()?.map(|profile| profile.athlete_id);
+    // Strava validates the callback while the subscription is being created, so
+    // keep serving without one, but reject every event until restarted
+    let subscription_id = subscription_id()
+        .await
+        .context("Failed to look up the push subscription")?;
+    if subscription_id.is_none() {
+        eprintln!(
+            "{}",
+            "⚠️  No push subscription yet, so events are rejected. \
+             Run 'chain-life webhook subscribe', then restart 'webhook serve'."
+                .yellow()
+                .bold()
+        );
+    }
+    let state = WebhookState {
+        verify_token: Arc::new(verify_token),
+        athlete_id,
+        subscription_id,
+        cache_lock: Arc::new(Mutex::new(())),
+    };
+
+    let listener = tokio::net::TcpListener::bind(&addr)
+        .await
+        .with_context(|| format!("Failed to listen on {addr}"))?;
+
+    println!(
+        "{} {}",
+        "🪝 Receiving webhook events on".bright_cyan().bold(),
+        format!("http://{}/webhook", listener.local_addr()?)
+            .blue()
+            .underline()
+    );
+    axum::serve(listener, router(state)).await?;
+    Ok(())
+}
+
+/// A push subscription of the application
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Subscription {
+    pub id: i64,
+    pub callback_url: Option<String>,
+    pub created_at: Option<String>,
+}
+
+/// Client ID and secret saved by `auth`, which the subscription API authenticates with
+fn client_credentials() -> Result<(String, String)> {
+    let credentials =
+        Credentials::load()?.context("No saved credentials. Run 'chain-life auth' first")?;
+    Ok((credentials.client_id, credentials.client_secret))
+}
+
+async fn check(response: reqwest::Response) -> Result<reqwest::Response> {
+    if !response.status().is_success() {
+        let error_text = response.text().await?;
+        return Err(anyhow::anyhow!("Strava API error: {error_text}"));
+    }
+    Ok(response)
+}
+
+pub async fn handle_subscribe(callback_url: String, verify_token: String) -> Result<()> {
+    let (client_id, client_secret) = client_credentials()?;
+    let params = [
+        ("client_id", client_id.as_str()),
+        ("client_secret", client_secret.as_str()),
+        ("callback_url", callback_url.as_str()),
+        ("verify_token", verify_token.as_str()),
+    ];
+
+    let response = reqwest::Client::new()
+        .post(format!("{API_BASE}/push_subscriptions"))
+        .form(&params)
+        .send()
+        .await?;
+    let subscription: Subscription = check(response).await?.json().await?;
+    // Kept so 'webhook serve' can tell our events from forged ones
+    store::save(SUBSCRIPTION_FILE, &subscription)?;
+
+    println!(
+        "{} {}",
+        "✅ Created subscription".bright_green().bold(),
+        subscription.id.to_string().bright_white().bold()
+    );
+    Ok(())
+}
+
+async fn list_subscriptions() -> Result<Vec<Subscription>> {
+    let (client_id, client_secret) = client_credentials()?;
+    let response = reqwest::Client::new()
+        .get(format!("{API_BASE}/push_subscriptions"))
+        .query(&[("client_id", client_id), ("client_secret", client_secret)])
+        .send()
+        .await?;
+    Ok(check(response).await?.json().await?)
+}
+
+pub async fn handle_list() -> Result<()> {
+    let subscriptions = list_subscriptions().await?;
+
+    if subscriptions.is_empty() {
+        println!("{}", "No subscriptions".yellow());
+        return Ok(());
+    }
+
+    for subscription in subscriptions {
+        println!(
+            "{}  {}  {}",
+            subscription.id.to_string().bright_white().bold(),
+            subscription.callback_url.unwrap_or_default().blue(),
+            subscription.created_at.unwrap_or_default().dimmed()
+        );
+    }
+    Ok(())
+}
+
+pub async fn handle_unsubscribe(id: i64) -> Result<()> {
+    let (client_id, client_secret) = client_credentials()?;
+    let response = reqwest::Client::new()
+        .delete(format!("{API_BASE}/push_subscriptions/{id}"))
+        .query(&[("client_id", client_id), ("client_secret", client_secret)])
+        .send()
+        .await?;
+    check(response).await?;
+    if store::load::<Option<Subscription>>(SUBSCRIPTION_FILE)?.is_some_and(|saved| saved.id == id) {
+        store::remove(SUBSCRIPTION_FILE)?;
+    }
+
+    println!(
+        "{} {}",
+        "🗑️  Deleted subscription".bright_green().bold(),
+        id.to_string().bright_white()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(object_type: &str, aspect_type: &str, updates: serde_json::Value) -> Event {
+        serde_json::from_value(serde_json::json!({
+            "object_type": object_type,
+            "object_id": 1360128428,
+            "aspect_type": aspect_type,
+            "owner_id": 134815,
+            "subscription_id": 120475,
+            "event_time": 1516126040,
+            "updates": updates
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_activity_event_actions() {
+        let no_updates = serde_json::json!({});
+        assert_eq!(
+            event("activity", "create", no_updates.clone()).action(None),
+            Action::Upsert(1360128428)
+        );
+        assert_eq!(
+            event(
+                "activity",
+                "update",
+                serde_json::json!({"type": "VirtualRide"})
+            )
+            .action(Some(134815)),
+            Action::Upsert(1360128428)
+        );
+        assert_eq!(
+            event("activity", "delete", no_updates.clone()).action(None),
+            Action::Remove(1360128428)
+        );
+        assert_eq!(
+            event("activity", "create", no_updates).action(Some(1)),
+            Action::Ignore
+        );
+    }
+
+    #[test]
+    fn test_athlete_event_actions() {
+        assert_eq!(
+            event(
+                "athlete",
+                "update",
+                serde_json::json!({"authorized": "false"})
+            )
+            .action(None),
+            Action::Deauthorized
+        );
+        assert_eq!(
+            event("athlete", "update", serde_json::json!({"weight": 70})).action(None),
+            Action::Ignore
+        );
+    }
+
+    #[test]
+    fn test_event_subscription() {
+        let event = event("activity", "delete", serde_json::json!({}));
+        assert!(event.is_for(Some(120475)));
+        assert!(!event.is_for(Some(1)));
+        assert!(!event.is_for(None));
+    }
+
+    #[test]
+    fn test_event_without_updates() {
+        let event: Event = serde_json::from_str(
+            r#"{"object_type":"activity","object_id":1,"aspect_type":"delete","owner_id":2,"subscription_id":3,"event_time":4}"#,
+        )
+        .unwrap();
+        assert!(event.updates.is_empty());
+        assert_eq!(event.action(None), Action::Remove(1));
+    }
+}
@@ -23,7 +23,8 @@ fn test_auth_subcommand_help() {
         .success()
         .stdout(predicate::str::contains("Authenticate with Strava using OAuth"))
         .stdout(predicate::str::contains("--client-id"))
-        .stdout(predicate::str::contains("--client-secret"));
+        .stdout(predicate::str::contains("STRAVA_CLIENT_SECRET"))
+        .stdout(predicate::str::contains("--client-secret").not());
 }
 
 #[test]
@@ -41,7 +42,9 @@ fn test_fetch_subcommand_help() {
 #[test]
 fn test_auth_missing_client_id() {
     let mut cmd = Command::cargo_bin("chain-life").unwrap();
-    cmd.arg("auth").arg("--client-secret").arg("secret123");
+    cmd.env_remove("STRAVA_CLIENT_ID")
+        .env("STRAVA_CLIENT_SECRET", "secret123")
+        .arg("auth");
     
     cmd.assert()
         .failure()
@@ -51,11 +54,12 @@ fn test_auth_missing_client_id() {
 #[test]
 fn test_auth_missing_client_secret() {
     let mut cmd = Command::cargo_bin("chain-life").unwrap();
-    cmd.arg("auth").arg("--client-id").arg("12345");
+    cmd.env_remove("STRAVA_CLIENT_SECRET")
+        .arg("auth").arg("--client-id").arg("12345");
     
     cmd.assert()
         .failure()
-        .stderr(predicate::str::contains("client-secret"));
+        .stderr(predicate::str::contains("No Client Secret: set STRAVA_CLIENT_SECRET"));
 }
 
 #[test]
@@ -69,13 +73,20 @@ fn test_fetch_missing_date() {
 }
 
 #[test]
-fn test_fetch_missing_token() {
-    let mut cmd = Command::cargo_bin("chain-life").unwrap();
-    cmd.arg("fetch").arg("--date").arg("2024-01-01");
-    
-    cmd.assert()
+fn test_fetch_without_token_or_credentials() {
+    let data_dir = std::env::temp_dir().join(format!("chain-life-no-token-{}", std::process::id()));
+    std::fs::create_dir_all(&data_dir).unwrap();
+
+    Command::cargo_bin("chain-life").unwrap()
+        .env("CHAIN_LIFE_DATA", &data_dir)
+        .env("CHAIN_LIFE_KEYRING", "off")
+        .arg("fetch").arg("--date").arg("2024-01-01")
+        .assert()
         .failure()
-        .stderr(predicate::str::contains("token"));
+        .stderr(predicate::str::contains("No saved credentials"))
+        .stderr(predicate::str::contains("--token"));
+
+    let _ = std::fs::remove_dir_all(&data_dir);
 }
 
 #[test]
@@ -119,4 +130,517 @@ fn test_invalid_subcommand() {
     cmd.assert()
         .failure()
         .stderr(predicate::str::contains("unrecognized subcommand"));
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_activity_show_help() {
+    let mut cmd = Command::cargo_bin("chain-life").unwrap();
+    cmd.arg("activity").arg("show").arg("--help");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Show detailed data for a single activity"))
+        .stdout(predicate::str::contains("--streams"))
+        .stdout(predicate::str::contains("--export"));
+}
+
+#[test]
+fn test_activity_show_invalid_format() {
+    let mut cmd = Command::cargo_bin("chain-life").unwrap();
+    cmd.arg("activity")
+        .arg("show").arg("123")
+        .arg("--token").arg("fake_token")
+        .arg("--format").arg("xml");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value 'xml'"));
+}
+
+#[test]
+fn test_fitness_subcommand_help() {
+    let mut cmd = Command::cargo_bin("chain-life").unwrap();
+    cmd.arg("fitness").arg("--help");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Compute training stress"))
+        .stdout(predicate::str::contains("--ftp"))
+        .stdout(predicate::str::contains("--threshold-hr"))
+        .stdout(predicate::str::contains("--concurrency"));
+}
+
+#[test]
+fn test_fitness_requires_threshold() {
+    let mut cmd = Command::cargo_bin("chain-life").unwrap();
+    cmd.env("CHAIN_LIFE_CONFIG", "/nonexistent/config.toml")
+        .arg("fitness")
+        .arg("--from").arg("2024-01-01")
+        .arg("--token").arg("fake_token");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("No FTP or threshold heart rate configured"));
+}
+
+#[test]
+fn test_goal_set_list_remove() {
+    let data_dir = std::env::temp_dir().join(format!("chain-life-goals-{}", std::process::id()));
+
+    Command::cargo_bin("chain-life").unwrap()
+        .env("CHAIN_LIFE_DATA", &data_dir)
+        .args(["goal", "set", "season", "--distance", "8000", "--year", "2025"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Goal saved"));
+
+    Command::cargo_bin("chain-life").unwrap()
+        .env("CHAIN_LIFE_DATA", &data_dir)
+        .args(["goal", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("season"))
+        .stdout(predicate::str::contains("2025-12-31"));
+
+    Command::cargo_bin("chain-life").unwrap()
+        .env("CHAIN_LIFE_DATA", &data_dir)
+        .args(["goal", "remove", "season"])
+        .assert()
+        .success();
+
+    Command::cargo_bin("chain-life").unwrap()
+        .env("CHAIN_LIFE_DATA", &data_dir)
+        .args(["goal", "remove", "season"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No goal named 'season'"));
+
+    let _ = std::fs::remove_dir_all(&data_dir);
+}
+
+#[test]
+fn test_goal_set_year_conflicts_with_range() {
+    let mut cmd = Command::cargo_bin("chain-life").unwrap();
+    cmd.args(["goal", "set", "season", "--distance", "8000", "--year", "2025", "--from", "2025-01-01", "--to", "2025-06-30"]);
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+#[test]
+fn test_fetch_invalid_chart() {
+    let mut cmd = Command::cargo_bin("chain-life").unwrap();
+    cmd.arg("fetch")
+        .arg("--date").arg("2024-01-01")
+        .arg("--token").arg("fake_token")
+        .arg("--chart").arg("pie");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value 'pie'"));
+}
+
+#[test]
+fn test_dashboard_subcommand_help() {
+    let mut cmd = Command::cargo_bin("chain-life").unwrap();
+    cmd.arg("dashboard").arg("--help");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Full-screen dashboard"))
+        .stdout(predicate::str::contains("--token"));
+}
+
+#[test]
+fn test_report_subcommand_help() {
+    let mut cmd = Command::cargo_bin("chain-life").unwrap();
+    cmd.arg("report").arg("--help");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Generate a season summary report"))
+        .stdout(predicate::str::contains("--out"))
+        .stdout(predicate::str::contains("html"));
+}
+
+#[test]
+fn test_serve_subcommand_help() {
+    let mut cmd = Command::cargo_bin("chain-life").unwrap();
+    cmd.arg("serve").arg("--help");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("--addr"))
+        .stdout(predicate::str::contains("--sync-interval"));
+}
+
+#[test]
+fn test_sync_without_credentials() {
+    let data_dir = std::env::temp_dir().join(format!("chain-life-sync-{}", std::process::id()));
+
+    Command::cargo_bin("chain-life").unwrap()
+        .env("CHAIN_LIFE_DATA", &data_dir)
+        .arg("sync")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No saved credentials"));
+
+    let _ = std::fs::remove_dir_all(&data_dir);
+}
+
+#[test]
+fn test_export_metrics_from_empty_cache() {
+    let data_dir = std::env::temp_dir().join(format!("chain-life-metrics-{}", std::process::id()));
+
+    Command::cargo_bin("chain-life").unwrap()
+        .env("CHAIN_LIFE_DATA", &data_dir)
+        .env("CHAIN_LIFE_CONFIG", data_dir.join("missing.toml"))
+        .arg("export-metrics")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("# TYPE chain_life_distance_meters gauge"));
+
+    let _ = std::fs::remove_dir_all(&data_dir);
+}
+
+#[test]
+fn test_webhook_subscribe_requires_verify_token() {
+    let mut cmd = Command::cargo_bin("chain-life").unwrap();
+    cmd.args(["webhook", "subscribe", "--callback-url", "https://example.com/webhook"]);
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("--verify-token"));
+}
+
+#[test]
+fn test_chain_measure_unknown_component() {
+    let data_dir = std::env::temp_dir().join(format!("chain-life-chain-{}", std::process::id()));
+
+    Command::cargo_bin("chain-life").unwrap()
+        .env("CHAIN_LIFE_DATA", &data_dir)
+        .env("CHAIN_LIFE_CONFIG", data_dir.join("missing.toml"))
+        .args(["chain", "measure", "KMC X11", "0.5"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No component named 'KMC X11'"));
+
+    let _ = std::fs::remove_dir_all(&data_dir);
+}
+
+#[test]
+fn test_chain_swap_and_rotation() {
+    let data_dir = std::env::temp_dir().join(format!("chain-life-rotation-{}", std::process::id()));
+
+    Command::cargo_bin("chain-life").unwrap()
+        .env("CHAIN_LIFE_DATA", &data_dir)
+        .args(["chain", "swap", "Wax 1", "--bike", "b1", "--date", "2025-01-01"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Chain swapped"));
+
+    Command::cargo_bin("chain-life").unwrap()
+        .env("CHAIN_LIFE_DATA", &data_dir)
+        .args(["chain", "rotation"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Wax 1"))
+        .stdout(predicate::str::contains("on b1"));
+
+    let _ = std::fs::remove_dir_all(&data_dir);
+}
+
+#[test]
+fn test_service_add_and_remove() {
+    let data_dir = std::env::temp_dir().join(format!("chain-life-service-{}", std::process::id()));
+
+    Command::cargo_bin("chain-life").unwrap()
+        .env("CHAIN_LIFE_DATA", &data_dir)
+        .args(["service", "add", "brake bleed", "--bike", "b1", "--cost", "45"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("#1 brake bleed"));
+
+    Command::cargo_bin("chain-life").unwrap()
+        .env("CHAIN_LIFE_DATA", &data_dir)
+        .args(["service", "remove", "1"])
+        .assert()
+        .success();
+
+    Command::cargo_bin("chain-life").unwrap()
+        .env("CHAIN_LIFE_DATA", &data_dir)
+        .args(["service", "history"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No service events logged"));
+
+    let _ = std::fs::remove_dir_all(&data_dir);
+}
+
+#[test]
+fn test_cost_with_empty_cache() {
+    let data_dir = std::env::temp_dir().join(format!("chain-life-cost-{}", std::process::id()));
+
+    Command::cargo_bin("chain-life").unwrap()
+        .env("CHAIN_LIFE_DATA", &data_dir)
+        .env("CHAIN_LIFE_CONFIG", data_dir.join("missing.toml"))
+        .args(["cost", "--year", "2025"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Spending and distance in 2025"))
+        .stderr(predicate::str::contains("No cached activities"));
+
+    let _ = std::fs::remove_dir_all(&data_dir);
+}
+
+#[test]
+fn test_gear_reconcile_without_cached_gear() {
+    let data_dir = std::env::temp_dir().join(format!("chain-life-gear-{}", std::process::id()));
+
+    Command::cargo_bin("chain-life").unwrap()
+        .env("CHAIN_LIFE_DATA", &data_dir)
+        .args(["gear", "reconcile"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Run 'chain-life gear list' first"));
+
+    let _ = std::fs::remove_dir_all(&data_dir);
+}
+
+#[test]
+fn test_gear_assign_trainer_conflicts_with_outdoor() {
+    let mut cmd = Command::cargo_bin("chain-life").unwrap();
+    cmd.args(["gear", "assign", "b1", "--from", "2025-01-01", "--trainer", "--outdoor"]);
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+#[test]
+fn test_gear_assign_without_write_scope() {
+    let data_dir = std::env::temp_dir().join(format!("chain-life-scope-{}", std::process::id()));
+    std::fs::create_dir_all(&data_dir).unwrap();
+    std::fs::write(
+        data_dir.join("credentials.json"),
+        r#"{"client_id": "12345", "client_secret": "secret", "access_token": "access",
+            "refresh_token": "refresh", "expires_at": 4102444800, "athlete_id": 1,
+            "athlete_name": null, "scopes": ["read", "activity:read_all"]}"#,
+    )
+    .unwrap();
+
+    Command::cargo_bin("chain-life").unwrap()
+        .env("CHAIN_LIFE_DATA", &data_dir)
+        .env("CHAIN_LIFE_KEYRING", "off")
+        .env_remove("CHAIN_LIFE_PASSPHRASE")
+        .args(["gear", "assign", "b1", "--from", "2025-01-01"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Credentials are still stored in plaintext"))
+        .stderr(predicate::str::contains("needs the activity:write scope"))
+        .stderr(predicate::str::contains("auth --scope read,activity:read_all,activity:write"));
+
+    let _ = std::fs::remove_dir_all(&data_dir);
+}
+
+#[test]
+fn test_plaintext_credentials_are_encrypted() {
+    let data_dir = std::env::temp_dir().join(format!("chain-life-vault-{}", std::process::id()));
+    std::fs::create_dir_all(&data_dir).unwrap();
+    std::fs::write(
+        data_dir.join("credentials.json"),
+        r#"{"client_id": "12345", "client_secret": "secret", "access_token": "access",
+            "refresh_token": "refresh", "expires_at": 4102444800, "athlete_id": 1,
+            "athlete_name": null, "scopes": ["read", "activity:read_all"]}"#,
+    )
+    .unwrap();
+
+    let run = || {
+        Command::cargo_bin("chain-life").unwrap()
+            .env("CHAIN_LIFE_DATA", &data_dir)
+            .env("CHAIN_LIFE_KEYRING", "off")
+            .env("CHAIN_LIFE_PASSPHRASE", "correct horse")
+            .args(["gear", "assign", "b1", "--from", "2025-01-01"])
+            .assert()
+            .failure()
+    };
+    run().stderr(predicate::str::contains("Moved the plaintext credentials to credentials.age"));
+    assert!(!data_dir.join("credentials.json").exists());
+    let encrypted = std::fs::read(data_dir.join("credentials.age")).unwrap();
+    assert!(!String::from_utf8_lossy(&encrypted).contains("refresh"));
+
+    // Loaded from the encrypted file the second time
+    run()
+        .stderr(predicate::str::contains("Moved").not())
+        .stderr(predicate::str::contains("needs the activity:write scope"));
+
+    Command::cargo_bin("chain-life").unwrap()
+        .env("CHAIN_LIFE_DATA", &data_dir)
+        .env("CHAIN_LIFE_KEYRING", "off")
+        .env("CHAIN_LIFE_PASSPHRASE", "wrong")
+        .args(["gear", "assign", "b1", "--from", "2025-01-01"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Failed to decrypt credentials.age"));
+
+    let _ = std::fs::remove_dir_all(&data_dir);
+}
+
+#[test]
+fn test_whoami() {
+    let data_dir = std::env::temp_dir().join(format!("chain-life-whoami-{}", std::process::id()));
+    std::fs::create_dir_all(&data_dir).unwrap();
+    let whoami = || {
+        Command::cargo_bin("chain-life").unwrap()
+            .env("CHAIN_LIFE_DATA", &data_dir)
+            .env("CHAIN_LIFE_KEYRING", "off")
+            .env("CHAIN_LIFE_PASSPHRASE", "correct horse")
+            .arg("whoami")
+            .assert()
+    };
+
+    whoami()
+        .failure()
+        .stderr(predicate::str::contains("Not logged in"));
+
+    std::fs::write(
+        data_dir.join("credentials.json"),
+        r#"{"client_id": "12345", "client_secret": "secret", "access_token": "access",
+            "refresh_token": "refresh", "expires_at": 1000, "athlete_id": 42,
+            "athlete_name": "Jane Doe", "scopes": ["read", "activity:read_all"]}"#,
+    )
+    .unwrap();
+    whoami()
+        .success()
+        .stdout(predicate::str::contains("Jane Doe"))
+        .stdout(predicate::str::contains("(#42)"))
+        .stdout(predicate::str::contains("read,activity:read_all"))
+        .stdout(predicate::str::contains("expired"))
+        .stdout(predicate::str::contains("credentials.age (passphrase-encrypted)"))
+        .stdout(predicate::str::contains("access").not());
+
+    // The athlete is read from the profile, without the passphrase
+    assert!(data_dir.join("athlete.json").exists());
+    std::fs::write(
+        data_dir.join("activities.json"),
+        r#"{"synced_at": 1700000000, "activities": []}"#,
+    )
+    .unwrap();
+    Command::cargo_bin("chain-life").unwrap()
+        .env("CHAIN_LIFE_DATA", &data_dir)
+        .env("CHAIN_LIFE_CONFIG", data_dir.join("missing.toml"))
+        .env("CHAIN_LIFE_KEYRING", "off")
+        .env_remove("CHAIN_LIFE_PASSPHRASE")
+        .arg("export-metrics")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(r#"athlete="42""#));
+
+    let _ = std::fs::remove_dir_all(&data_dir);
+}
+
+#[test]
+fn test_logout_without_credentials_keeps_caches() {
+    let data_dir = std::env::temp_dir().join(format!("chain-life-logout-{}", std::process::id()));
+    std::fs::create_dir_all(&data_dir).unwrap();
+    std::fs::write(data_dir.join("activities.json"), "{}").unwrap();
+    std::fs::write(data_dir.join("services.json"), "[]").unwrap();
+
+    Command::cargo_bin("chain-life").unwrap()
+        .env("CHAIN_LIFE_DATA", &data_dir)
+        .env("CHAIN_LIFE_KEYRING", "off")
+        .arg("logout")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Not logged in"))
+        .stdout(predicate::str::contains("Removed cached").not());
+    // Nothing was revoked, so nothing is removed
+    assert!(data_dir.join("activities.json").exists());
+    assert!(data_dir.join("services.json").exists());
+
+    let _ = std::fs::remove_dir_all(&data_dir);
+}
+
+#[test]
+fn test_auth_export_and_import() {
+    let base = std::env::temp_dir().join(format!("chain-life-bundle-{}", std::process::id()));
+    let (source, target) = (base.join("source"), base.join("target"));
+    std::fs::create_dir_all(&source).unwrap();
+    std::fs::write(
+        source.join("credentials.json"),
+        r#"{"client_id": "12345", "client_secret": "secret", "access_token": "access",
+            "refresh_token": "refresh", "expires_at": 4102444800, "athlete_id": 42,
+            "athlete_name": "Jane Doe", "scopes": ["read"]}"#,
+    )
+    .unwrap();
+    let bundle = base.join("bundle.age");
+    let chain_life = |data_dir: &std::path::Path, bundle_passphrase: &str| {
+        let mut cmd = Command::cargo_bin("chain-life").unwrap();
+        cmd.env("CHAIN_LIFE_DATA", data_dir)
+            .env("CHAIN_LIFE_KEYRING", "off")
+            .env("CHAIN_LIFE_PASSPHRASE", "correct horse")
+            .env("CHAIN_LIFE_BUNDLE_PASSPHRASE", bundle_passphrase)
+            .env_remove("STRAVA_CLIENT_ID");
+        cmd
+    };
+
+    chain_life(&source, "battery staple")
+        .args(["auth", "export", "--remove", "--out"])
+        .arg(&bundle)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Credentials exported to"))
+        .stderr(predicate::str::contains("Removed the credentials from this machine"));
+    assert!(!source.join("credentials.json").exists());
+    assert!(!source.join("credentials.age").exists());
+    let content = std::fs::read_to_string(&bundle).unwrap();
+    assert!(content.starts_with("-----BEGIN AGE ENCRYPTED FILE-----"));
+    assert!(!content.contains("refresh"));
+
+    chain_life(&target, "wrong")
+        .args(["auth", "import"])
+        .arg(&bundle)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Failed to decrypt the bundle"));
+
+    chain_life(&target, "battery staple")
+        .args(["auth", "import"])
+        .arg(&bundle)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Imported credentials for Jane Doe"));
+    chain_life(&target, "battery staple")
+        .arg("whoami")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("(#42)"));
+
+    let _ = std::fs::remove_dir_all(&base);
+}
+
+#[test]
+fn test_auth_refresh_token_conflicts_with_scope() {
+    let mut cmd = Command::cargo_bin("chain-life").unwrap();
+    cmd.args(["auth", "--client-id", "12345", "--refresh-token", "--scope", "read"]);
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+#[test]
+fn test_stats_without_credentials() {
+    let data_dir = std::env::temp_dir().join(format!("chain-life-stats-{}", std::process::id()));
+
+    Command::cargo_bin("chain-life").unwrap()
+        .env("CHAIN_LIFE_DATA", &data_dir)
+        .env("CHAIN_LIFE_KEYRING", "off")
+        .args(["stats", "--check"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No saved credentials"));
+
+    let _ = std::fs::remove_dir_all(&data_dir);
+}